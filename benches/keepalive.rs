@@ -0,0 +1,79 @@
+// Measures the per-call overhead of stashing return values with KeepAlive, compared to the
+// global RwLock-based stash that it replaced (reproduced below), for the value types most
+// commonly returned from exported functions.
+// Run with: cargo bench --bench keepalive
+
+use std::hint::black_box;
+use std::sync::RwLock;
+use std::time::Instant;
+use to_js::{clear_keepalive, IntoWasm, KeepAlive, Wasm};
+
+static RWLOCK_STASH: RwLock<Vec<Box<dyn Send + Sync + 'static>>> = RwLock::new(Vec::new());
+
+// Like KeepAlive::new, this encodes the value before stashing it, so that both variants do the same work
+fn rwlock_keepalive<T: Send + Sync + 'static>(x: T) -> Wasm
+where
+    for<'a> &'a T: IntoWasm,
+{
+    let wasm = (&x).into_wasm();
+    RWLOCK_STASH.write().unwrap().push(Box::new(x));
+    wasm
+}
+
+fn rwlock_clear() {
+    RWLOCK_STASH.write().unwrap().clear();
+}
+
+const ITERATIONS: u32 = 1_000_000;
+
+// Simulates ITERATIONS exported calls, each of which clears the stash and then stashes one value
+fn bench(name: &str, mut call: impl FnMut(u32)) {
+    // Warm up
+    for i in 0..ITERATIONS / 10 {
+        call(i);
+    }
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        call(black_box(i));
+    }
+    let elapsed = start.elapsed();
+    let per_call = elapsed.as_secs_f64() * 1e9 / ITERATIONS as f64;
+    println!("{name:<28} {per_call:>8.1} ns/call");
+}
+
+fn main() {
+    let string = "Hello from a String".to_string();
+    let bytes = vec![0u8; 64];
+    let floats = vec![0f64; 64];
+
+    bench("string (rwlock)", |_| {
+        rwlock_clear();
+        black_box(rwlock_keepalive(black_box(string.clone())));
+    });
+    bench("string (thread-local)", |_| {
+        clear_keepalive();
+        black_box(KeepAlive::new(black_box(string.clone())));
+    });
+
+    bench("Vec<u8> (rwlock)", |_| {
+        rwlock_clear();
+        black_box(rwlock_keepalive(black_box(bytes.clone())));
+    });
+    bench("Vec<u8> (thread-local)", |_| {
+        clear_keepalive();
+        black_box(KeepAlive::new(black_box(bytes.clone())));
+    });
+
+    bench("Vec<f64> (rwlock)", |_| {
+        rwlock_clear();
+        black_box(rwlock_keepalive(black_box(floats.clone())));
+    });
+    bench("Vec<f64> (thread-local)", |_| {
+        clear_keepalive();
+        black_box(KeepAlive::new(black_box(floats.clone())));
+    });
+
+    // Clearing alone, as done by exported functions that do not return stashed values
+    bench("clear only (rwlock)", |_| rwlock_clear());
+    bench("clear only (thread-local)", |_| clear_keepalive());
+}
@@ -0,0 +1,73 @@
+// Compares the per-call overhead of the wrappers that `wrap` compiles for each export with `new Function`
+// to that of the closure-based wrappers used when code generation is disallowed (`compile: false`).
+// The WebAssembly instance is mocked, so this measures only the JavaScript side of each call.
+// Run with: node benches/wrap.mjs
+
+import { readFileSync } from "node:fs";
+
+const code = readFileSync(new URL("../src/lib.js", import.meta.url), "utf8");
+const { wrap } = new Function(`${code}\nreturn { wrap };`)();
+const ABI_VERSION = Number(/const ABI_VERSION = (\d+);/.exec(code)[1]);
+
+// Encode type info and (ptr, len) pairs in the same way as the Rust side
+const f64 = new Float64Array(1);
+const u32 = new Uint32Array(f64.buffer);
+const u8 = new Uint8Array(f64.buffer);
+
+function info(isResult, isOption, isArray, arrayType, transform) {
+	u8.set([isResult, isOption, isArray, arrayType, transform, 0, 0, 0]);
+	return f64[0];
+}
+
+function pair(a, b) {
+	u32.set([a, b]);
+	return f64[0];
+}
+
+const memory = new WebAssembly.Memory({ initial: 1 });
+new Uint8Array(memory.buffer).set(new TextEncoder().encode("Hello from a &'static str"), 64);
+const slice = pair(128, 16);
+const str = pair(64, 25);
+const none = pair(0, 0xfff80000);
+
+// In enum variant order (ArrayType: U32 = 4, F64 = 9, None = 10; Transform: Identity = 9, String = 12)
+const exports = {
+	memory,
+	to_js_abi_version: () => ABI_VERSION,
+	add: (a, b) => a + b,
+	add_info_: () => info(0, 0, 0, 10, 9),
+	checked_add: (a, b) => (a + b < 2 ** 32 ? a + b : none),
+	checked_add_info_: () => info(0, 1, 0, 4, 9),
+	slice: () => slice,
+	slice_info_: () => info(0, 0, 1, 9, 9),
+	str: () => str,
+	str_info_: () => info(0, 0, 1, 0, 12),
+};
+
+const ITERATIONS = 1_000_000;
+
+const calls = {
+	add: (rs, i) => rs.add(i, 1),
+	checked_add: (rs, i) => rs.checked_add(i, 1),
+	slice: (rs) => rs.slice(),
+	str: (rs) => rs.str(),
+};
+
+function bench(name, rs, call) {
+	let sink;
+	// Warm up
+	for (let i = 0; i < ITERATIONS / 10; i++) sink = call(rs, i);
+	const start = performance.now();
+	for (let i = 0; i < ITERATIONS; i++) sink = call(rs, i);
+	const perCall = ((performance.now() - start) * 1e6) / ITERATIONS;
+	console.log(`${name.padEnd(28)} ${perCall.toFixed(1).padStart(8)} ns/call`);
+	return sink;
+}
+
+const compiled = wrap({ exports }, false, { compile: true });
+const closures = wrap({ exports }, false, { compile: false });
+
+for (const [name, call] of Object.entries(calls)) {
+	bench(`${name} (compiled)`, compiled, call);
+	bench(`${name} (closures)`, closures, call);
+}
@@ -0,0 +1,54 @@
+// Writes the JavaScript glue code (lib.js) to OUT_DIR, from where it is embedded into the module (see `JS` in
+// lib.rs), and a copy without the code that generates wrappers at runtime (between the `begin: runtime wrappers`
+// and `end: runtime wrappers` comments), which is embedded into the glue code generated by the `to_js` binary.
+// With the `minify` feature, release builds embed copies with comments and unnecessary whitespace removed (see
+// build/minify.rs).
+
+use std::path::Path;
+
+#[path = "build/minify.rs"]
+mod minify;
+
+fn main() {
+    println!("cargo::rerun-if-changed=src/lib.js");
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rerun-if-changed=build/minify.rs");
+
+    let code = std::fs::read_to_string("src/lib.js").expect("failed to read src/lib.js");
+    let minify = std::env::var_os("CARGO_FEATURE_MINIFY").is_some()
+        && std::env::var("PROFILE").is_ok_and(|profile| profile == "release");
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    for (name, code) in [
+        ("lib.js", code.clone()),
+        ("lib.precompiled.js", without_runtime_wrappers(&code)),
+    ] {
+        let code = if minify {
+            minify::minify_js(&code)
+        } else {
+            code
+        };
+        std::fs::write(Path::new(&out_dir).join(name), code).expect("failed to write glue code");
+    }
+}
+
+// Remove the lines from each `begin: runtime wrappers` comment to the following `end: runtime wrappers` comment
+fn without_runtime_wrappers(code: &str) -> String {
+    let mut out = String::with_capacity(code.len());
+    let mut skipping = false;
+    for line in code.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("// begin: runtime wrappers") {
+            skipping = true;
+        } else if trimmed.starts_with("// end: runtime wrappers") {
+            skipping = false;
+        } else if !skipping {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    assert!(
+        !skipping,
+        "unterminated `begin: runtime wrappers` comment in lib.js"
+    );
+    out
+}
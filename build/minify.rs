@@ -0,0 +1,246 @@
+// A minifier for the JavaScript glue code, used by build.rs (and included by the tests in tests/minify.rs and
+// tests/node.rs).
+//
+// The minifier is deliberately simple: it does not rename anything or rewrite expressions. String and template
+// literals (including the code generated in templates by `wrapperSource`) are copied verbatim, and line breaks
+// are only removed where they cannot be significant for automatic semicolon insertion.
+
+pub fn minify_js(code: &str) -> String {
+    let chars: Vec<char> = code.chars().collect();
+    let mut m = Minifier {
+        chars: &chars,
+        pos: 0,
+        out: String::with_capacity(code.len()),
+        pending_space: false,
+        pending_newline: false,
+        parens: Vec::new(),
+        closed_condition: false,
+    };
+    m.code(false);
+    m.out.push('\n');
+    m.out
+}
+
+struct Minifier<'a> {
+    chars: &'a [char],
+    pos: usize,
+    out: String,
+    // Whether whitespace (or a line break) was skipped since the last character that was written
+    pending_space: bool,
+    pending_newline: bool,
+    // For each open parenthesis, whether it encloses the condition of a statement such as `if`, and whether the
+    // last one that was closed did, after which a slash begins a regular expression rather than a division
+    parens: Vec<bool>,
+    closed_condition: bool,
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+impl Minifier<'_> {
+    fn peek(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    // Minify code until the end of the input or, if `in_template` is true, until the `}` that closes a
+    // template literal substitution (which is consumed and written).
+    fn code(&mut self, in_template: bool) {
+        let mut depth = 0;
+        while let Some(c) = self.peek(0) {
+            match c {
+                '/' if self.peek(1) == Some('/') => {
+                    while self.peek(0).is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                }
+                '/' if self.peek(1) == Some('*') => {
+                    self.pos += 2;
+                    while self.pos < self.chars.len()
+                        && !(self.peek(0) == Some('*') && self.peek(1) == Some('/'))
+                    {
+                        if self.peek(0) == Some('\n') {
+                            self.pending_newline = true;
+                        }
+                        self.pos += 1;
+                    }
+                    self.pos += 2;
+                    self.pending_space = true;
+                }
+                '\n' => {
+                    self.pending_newline = true;
+                    self.pos += 1;
+                }
+                c if c.is_whitespace() => {
+                    self.pending_space = true;
+                    self.pos += 1;
+                }
+                '"' | '\'' => {
+                    self.separate(c);
+                    self.string(c);
+                }
+                '`' => {
+                    self.separate(c);
+                    self.template();
+                }
+                '/' if self.regex_allowed() => {
+                    self.separate(c);
+                    self.regex();
+                }
+                _ => {
+                    if c == '(' {
+                        let condition = matches!(self.last_word(), "if" | "while" | "for" | "with");
+                        self.parens.push(condition);
+                    } else if c == ')' {
+                        self.closed_condition = self.parens.pop().unwrap_or(false);
+                    }
+                    if c == '{' {
+                        depth += 1;
+                    } else if c == '}' {
+                        if in_template && depth == 0 {
+                            // Whitespace before the closing brace of a substitution is not significant
+                            self.pending_space = false;
+                            self.pending_newline = false;
+                            self.out.push(c);
+                            self.pos += 1;
+                            return;
+                        }
+                        depth -= 1;
+                    }
+                    self.separate(c);
+                    self.out.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    // Write the whitespace (if any) needed between the previously written character and `next`
+    fn separate(&mut self, next: char) {
+        let prev = self.out.chars().next_back();
+        let newline = std::mem::take(&mut self.pending_newline);
+        let space = std::mem::take(&mut self.pending_space);
+        let Some(prev) = prev else { return };
+        if newline && !"{([,;:=&|?*<>".contains(prev) && !"})].,;:?".contains(next) {
+            // Keep line breaks that may terminate a statement
+            self.out.push('\n');
+        } else if (newline || space)
+            && ((is_word(prev) && is_word(next))
+                || (prev == next && "+-".contains(prev))
+                || (prev == '/' && next == '/'))
+        {
+            // Keep tokens separate, eg. `return x` or `a + +b`
+            self.out.push(' ');
+        }
+    }
+
+    fn string(&mut self, quote: char) {
+        self.out.push(quote);
+        self.pos += 1;
+        while let Some(c) = self.peek(0) {
+            self.out.push(c);
+            self.pos += 1;
+            if c == '\\' {
+                if let Some(c) = self.peek(0) {
+                    self.out.push(c);
+                    self.pos += 1;
+                }
+            } else if c == quote {
+                return;
+            }
+        }
+    }
+
+    fn template(&mut self) {
+        self.out.push('`');
+        self.pos += 1;
+        while let Some(c) = self.peek(0) {
+            self.out.push(c);
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    if let Some(c) = self.peek(0) {
+                        self.out.push(c);
+                        self.pos += 1;
+                    }
+                }
+                '`' => return,
+                '$' if self.peek(0) == Some('{') => {
+                    self.out.push('{');
+                    self.pos += 1;
+                    self.code(true);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // The word (identifier or keyword) at the end of the output, if any
+    fn last_word(&self) -> &str {
+        let start = self
+            .out
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| is_word(c))
+            .last()
+            .map_or(self.out.len(), |(i, _)| i);
+        &self.out[start..]
+    }
+
+    // A slash begins a regular expression literal (rather than being a division operator) if it cannot follow
+    // an operand, ie. if it follows an operator or punctuation, the condition of a statement such as `if`, or a
+    // keyword such as `return`.
+    fn regex_allowed(&self) -> bool {
+        let Some(prev) = self.out.chars().next_back() else {
+            return true;
+        };
+        if prev == ')' {
+            return self.closed_condition;
+        }
+        if !is_word(prev) {
+            return !"]}".contains(prev);
+        }
+        matches!(
+            self.last_word(),
+            "return"
+                | "typeof"
+                | "instanceof"
+                | "in"
+                | "of"
+                | "new"
+                | "delete"
+                | "void"
+                | "throw"
+                | "case"
+                | "do"
+                | "else"
+        )
+    }
+
+    fn regex(&mut self) {
+        self.out.push('/');
+        self.pos += 1;
+        let mut in_class = false;
+        while let Some(c) = self.peek(0) {
+            self.out.push(c);
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    if let Some(c) = self.peek(0) {
+                        self.out.push(c);
+                        self.pos += 1;
+                    }
+                }
+                '[' => in_class = true,
+                ']' => in_class = false,
+                '/' if !in_class => break,
+                _ => {}
+            }
+        }
+        // Flags
+        while self.peek(0).is_some_and(is_word) {
+            self.out.push(self.chars[self.pos]);
+            self.pos += 1;
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use syn::{
     parse::{Parse, ParseStream},
-    parse_macro_input, Ident, ItemFn, LitStr, Result, ReturnType,
+    parse_macro_input, ForeignItem, Ident, ItemFn, ItemForeignMod, LitStr, Result, ReturnType,
 };
 extern crate proc_macro;
 
@@ -9,6 +9,7 @@ use quote::quote;
 
 struct JsArgs {
     prefix: Option<String>,
+    preserve_keepalive: bool,
 }
 
 impl Parse for JsArgs {
@@ -18,13 +19,26 @@ impl Parse for JsArgs {
     /// Note that this will prefix not only the export but also the name of the function
     /// on the Rust side, avoiding name collisions if a function with the same name is
     /// exported multiple times, as it might be during a macro-driven generation process.
+    /// Also parses the #[js(preserve_keepalive)] flag, which exports the function without clearing
+    /// the values kept alive by previous calls (see to_js!).
     fn parse(input: ParseStream) -> Result<Self> {
         let mut name_prefix = None;
+        let mut preserve_keepalive = false;
 
-        // Parse the input stream to extract prefix argument if provided
+        // Parse the input stream to extract prefix argument and flags if provided
         while !input.is_empty() {
             let lookahead = input.lookahead1();
-            if lookahead.peek(syn::Ident) {
+            if lookahead.peek(syn::Ident) && !input.peek2(syn::Token![=]) {
+                let ident: Ident = input.parse()?;
+                if ident == "preserve_keepalive" {
+                    preserve_keepalive = true;
+                } else {
+                    return Err(syn::Error::new(
+                        ident.span(),
+                        format!("unknown flag: {}", ident),
+                    ));
+                }
+            } else if lookahead.peek(syn::Ident) {
                 let ident: Ident = input.parse()?;
                 let _eq_token: syn::Token![=] = input.parse()?;
                 let value: LitStr = input.parse()?;
@@ -47,6 +61,7 @@ impl Parse for JsArgs {
 
         Ok(JsArgs {
             prefix: name_prefix,
+            preserve_keepalive,
         })
     }
 }
@@ -77,13 +92,69 @@ pub fn js(attr: TokenStream, input: TokenStream) -> TokenStream {
         );
     }
 
+    // Async functions are exported as functions that spawn their future on the to_js
+    // executor and return a JsPromise, which resolves to the future's output.
+    if item_fn.sig.asyncness.take().is_some() {
+        if let ReturnType::Type(_, ret) = &item_fn.sig.output {
+            let block = &item_fn.block;
+            let ret = ret.clone();
+            item_fn.block = syn::parse_quote!({ to_js::JsPromise::spawn(async move #block) });
+            item_fn.sig.output = syn::parse_quote!(-> to_js::JsPromise<#ret>);
+        }
+    }
+
     // Apply the macro_rules! macro to the function item's tokens
-    let expanded = quote! {
-        to_js::to_js! {
-            #item_fn
+    let expanded = if args.preserve_keepalive {
+        quote! {
+            to_js::to_js! {
+                @preserve_keepalive
+                #item_fn
+            }
+        }
+    } else {
+        quote! {
+            to_js::to_js! {
+                #item_fn
+            }
         }
     };
 
     // Parse the expanded tokens back into a TokenStream
     expanded.into()
 }
+
+/// Declares JavaScript functions that can be called from Rust, given as function
+/// declarations inside an extern block, eg. #[js_import] extern "C" { fn log(s: &str); }
+#[proc_macro_attribute]
+pub fn js_import(_attr: TokenStream, input: TokenStream) -> TokenStream {
+    let foreign_mod: ItemForeignMod = parse_macro_input!(input);
+
+    let mut fns = Vec::new();
+    for item in foreign_mod.items {
+        let ForeignItem::Fn(item_fn) = item else {
+            return syn::Error::new_spanned(item, "only function declarations can be imported")
+                .to_compile_error()
+                .into();
+        };
+        let attrs = &item_fn.attrs;
+        let vis = &item_fn.vis;
+        let name = &item_fn.sig.ident;
+        let inputs = &item_fn.sig.inputs;
+        // The from_js! macro requires functions to have an explicit return type
+        let output = match &item_fn.sig.output {
+            ReturnType::Default => quote!(()),
+            ReturnType::Type(_, ty) => quote!(#ty),
+        };
+        fns.push(quote! {
+            #(#attrs)* #vis fn #name(#inputs) -> #output;
+        });
+    }
+
+    let expanded = quote! {
+        to_js::from_js! {
+            #(#fns)*
+        }
+    };
+
+    expanded.into()
+}
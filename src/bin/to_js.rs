@@ -0,0 +1,883 @@
+// Generates JavaScript glue code for a WebAssembly module built with to_js, ahead of time.
+//
+// Rather than calling each `name_info_` export and compiling a wrapper with `new Function` at runtime, this reads
+// the type info of each export from the compiled .wasm file and emits an ES module (a drop-in replacement for the
+// module embedded as `JS`) containing a wrapper specialized for each of the module's exports. Optionally, it also
+// writes out a copy of the .wasm file with the `_info_` exports removed, since they are no longer needed.
+//
+// It can also write out a Python module for calling the module's exports from Python (see host.py), which includes
+// the type info of each export.
+//
+// Usage: to_js <input.wasm> [--out <glue.js>] [--strip <output.wasm>] [--python <module.py>]
+//
+// The glue code is written to standard output if there is no `--out`, unless only a Python module is wanted.
+//
+// Note: The type info is read from the module's `to_js_manifest` custom section (see manifest.rs). For modules
+// without one, it is determined by statically evaluating the body of each `_info_` function, which requires
+// that it has been optimized down to a constant, as is the case in release builds.
+
+use std::fmt::Write;
+use std::process::ExitCode;
+
+type Result<T> = std::result::Result<T, String>;
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+struct Args {
+    input: String,
+    out: Option<String>,
+    strip: Option<String>,
+    python: Option<String>,
+}
+
+fn parse_args() -> Result<Args> {
+    const USAGE: &str =
+        "usage: to_js <input.wasm> [--out <glue.js>] [--strip <output.wasm>] [--python <module.py>]";
+    let mut input = None;
+    let mut out = None;
+    let mut strip = None;
+    let mut python = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--out" => out = Some(args.next().ok_or(USAGE)?),
+            "--strip" => strip = Some(args.next().ok_or(USAGE)?),
+            "--python" => python = Some(args.next().ok_or(USAGE)?),
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => return Err(format!("unexpected argument: {arg}\n{USAGE}")),
+        }
+    }
+    let input = input.ok_or(USAGE)?;
+    Ok(Args {
+        input,
+        out,
+        strip,
+        python,
+    })
+}
+
+fn run() -> Result<()> {
+    let args = parse_args()?;
+    let bytes = std::fs::read(&args.input).map_err(|e| format!("{}: {e}", args.input))?;
+    let module = Module::parse(&bytes)?;
+
+    match &args.out {
+        Some(path) => {
+            let glue = generate(&module, &bytes, &args.input)?;
+            std::fs::write(path, glue).map_err(|e| format!("{path}: {e}"))?;
+        }
+        None if args.python.is_none() => print!("{}", generate(&module, &bytes, &args.input)?),
+        None => {}
+    }
+
+    if let Some(path) = &args.python {
+        let python = generate_python(&module, &bytes, &args.input)?;
+        std::fs::write(path, python).map_err(|e| format!("{path}: {e}"))?;
+    }
+
+    if let Some(path) = &args.strip {
+        let stripped = module.strip_info_exports(&bytes);
+        std::fs::write(path, stripped).map_err(|e| format!("{path}: {e}"))?;
+    }
+    Ok(())
+}
+
+// Parsing
+//
+
+// The parts of a WebAssembly module needed to find each export's number of arguments and type info
+struct Module {
+    /// Number of parameters of each function type
+    types: Vec<usize>,
+    /// Number of imported functions, which come first in the function index space
+    num_func_imports: u32,
+    /// Type index of each function defined in the module
+    funcs: Vec<u32>,
+    exports: Vec<Export>,
+    /// Body of each function defined in the module
+    bodies: Vec<std::ops::Range<usize>>,
+    /// Byte range of the export section, including its id and size
+    export_section: Option<std::ops::Range<usize>>,
+    /// Contents of the `to_js_manifest` custom section(s), if any
+    manifest: Vec<u8>,
+}
+
+struct Export {
+    name: String,
+    kind: u8,
+    index: u32,
+}
+
+struct ManifestEntry<'a> {
+    kind: u8,
+    name: &'a str,
+    infos: Vec<[u8; 8]>,
+}
+
+const EXPORT_FUNC: u8 = 0;
+
+// The kinds of manifest entries, which describe exports and imports (see manifest.rs)
+const MANIFEST_EXPORT: u8 = 0;
+const MANIFEST_IMPORT: u8 = 1;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8> {
+        let b = *self.bytes.get(self.pos).ok_or("unexpected end of module")?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or("unexpected end of module")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    // Unsigned LEB128
+    fn u64(&mut self) -> Result<u64> {
+        let mut result = 0;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            result |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+            if shift >= 64 {
+                return Err("invalid LEB128 integer".into());
+            }
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        u32::try_from(self.u64()?).map_err(|_| "integer too large".into())
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| "invalid UTF-8 in name".into())
+    }
+
+    fn limits(&mut self) -> Result<()> {
+        let flags = self.byte()?;
+        self.u64()?;
+        if flags & 1 != 0 {
+            self.u64()?;
+        }
+        Ok(())
+    }
+}
+
+impl Module {
+    fn parse(bytes: &[u8]) -> Result<Module> {
+        if bytes.get(0..8) != Some(b"\0asm\x01\0\0\0") {
+            return Err("not a WebAssembly module".into());
+        }
+        let mut module = Module {
+            types: Vec::new(),
+            num_func_imports: 0,
+            funcs: Vec::new(),
+            exports: Vec::new(),
+            bodies: Vec::new(),
+            export_section: None,
+            manifest: Vec::new(),
+        };
+        let mut r = Reader { bytes, pos: 8 };
+        while r.pos < bytes.len() {
+            let start = r.pos;
+            let id = r.byte()?;
+            let len = r.u32()? as usize;
+            let end = r.pos + len;
+            match id {
+                // Custom section
+                0 if r.name()? == "to_js_manifest" => {
+                    module.manifest.extend_from_slice(r.bytes(end - r.pos)?);
+                }
+                1 => module.parse_types(&mut r)?,
+                2 => module.parse_imports(&mut r)?,
+                3 => {
+                    for _ in 0..r.u32()? {
+                        module.funcs.push(r.u32()?);
+                    }
+                }
+                7 => {
+                    module.export_section = Some(start..end);
+                    for _ in 0..r.u32()? {
+                        let name = r.name()?;
+                        let kind = r.byte()?;
+                        let index = r.u32()?;
+                        module.exports.push(Export { name, kind, index });
+                    }
+                }
+                10 => {
+                    for _ in 0..r.u32()? {
+                        let size = r.u32()? as usize;
+                        module.bodies.push(r.pos..r.pos + size);
+                        r.pos += size;
+                    }
+                }
+                _ => {}
+            }
+            r.pos = end;
+        }
+        Ok(module)
+    }
+
+    fn parse_types(&mut self, r: &mut Reader) -> Result<()> {
+        for _ in 0..r.u32()? {
+            if r.byte()? != 0x60 {
+                return Err("unsupported type in type section".into());
+            }
+            let num_params = r.u32()? as usize;
+            r.bytes(num_params)?;
+            let num_results = r.u32()? as usize;
+            r.bytes(num_results)?;
+            self.types.push(num_params);
+        }
+        Ok(())
+    }
+
+    fn parse_imports(&mut self, r: &mut Reader) -> Result<()> {
+        for _ in 0..r.u32()? {
+            r.name()?;
+            r.name()?;
+            match r.byte()? {
+                // Function
+                0 => {
+                    r.u32()?;
+                    self.num_func_imports += 1;
+                }
+                // Table
+                1 => {
+                    r.byte()?;
+                    r.limits()?;
+                }
+                // Memory
+                2 => r.limits()?,
+                // Global
+                3 => {
+                    r.byte()?;
+                    r.byte()?;
+                }
+                // Tag
+                4 => {
+                    r.byte()?;
+                    r.u32()?;
+                }
+                kind => return Err(format!("unsupported import kind {kind}")),
+            }
+        }
+        Ok(())
+    }
+
+    fn export(&self, name: &str) -> Option<&Export> {
+        self.exports
+            .iter()
+            .find(|d| d.name == name && d.kind == EXPORT_FUNC)
+    }
+
+    // Returns the body of a function defined in the module (rather than imported)
+    fn body<'a>(&self, bytes: &'a [u8], func: u32) -> Option<&'a [u8]> {
+        let index = func.checked_sub(self.num_func_imports)? as usize;
+        Some(&bytes[self.bodies.get(index)?.clone()])
+    }
+
+    fn num_params(&self, func: u32) -> Option<usize> {
+        let index = func.checked_sub(self.num_func_imports)? as usize;
+        self.types.get(*self.funcs.get(index)? as usize).copied()
+    }
+
+    // The exports of #[js] functions, which are the function exports with a companion `_info_` export
+    fn js_exports(&self) -> impl Iterator<Item = (&Export, &Export)> {
+        self.exports.iter().filter_map(|info| {
+            let name = info.name.strip_suffix("_info_")?;
+            Some((self.export(name)?, info))
+        })
+    }
+
+    // Returns the #[js] exports and the type info of each one, preferring the manifest if there is one
+    fn type_infos(&self, bytes: &[u8]) -> Result<Vec<(&Export, [u8; 8])>> {
+        if !self.manifest.is_empty() {
+            return self.manifest_exports();
+        }
+        self.js_exports()
+            .map(|(export, info)| {
+                let body = self
+                    .body(bytes, info.index)
+                    .ok_or_else(|| format!("`{}` is not defined in the module", info.name))?;
+                let type_info = evaluate_info(body).ok_or_else(|| {
+                    format!(
+                        "could not evaluate `{}`, which should return a constant (is this a release build?)",
+                        info.name
+                    )
+                })?;
+                Ok((export, type_info))
+            })
+            .collect()
+    }
+
+    // Reads the entries of the manifest: kind (u8), name length (u32 LE), name, info count (u8), and 8 bytes of
+    // info per count
+    fn manifest_entries(&self) -> Result<Vec<ManifestEntry<'_>>> {
+        let mut entries = Vec::new();
+        let mut r = Reader {
+            bytes: &self.manifest,
+            pos: 0,
+        };
+        while r.pos < self.manifest.len() {
+            let kind = r.byte()?;
+            let name_len = u32::from_le_bytes(r.bytes(4)?.try_into().unwrap()) as usize;
+            let name =
+                std::str::from_utf8(r.bytes(name_len)?).map_err(|_| "invalid UTF-8 in manifest")?;
+            let count = r.byte()? as usize;
+            let infos = r.bytes(8 * count)?;
+            let infos = infos.chunks(8).map(|info| info.try_into().unwrap());
+            entries.push(ManifestEntry {
+                kind,
+                name,
+                infos: infos.collect(),
+            });
+        }
+        Ok(entries)
+    }
+
+    // The export entries of the manifest, which describe the return value of each #[js] function
+    fn manifest_exports(&self) -> Result<Vec<(&Export, [u8; 8])>> {
+        let mut exports = Vec::new();
+        for ManifestEntry { kind, name, infos } in self.manifest_entries()? {
+            if kind == MANIFEST_EXPORT {
+                let export = self
+                    .export(name)
+                    .ok_or_else(|| format!("`{name}` is in the manifest but is not exported"))?;
+                let [info] = infos[..] else {
+                    return Err(format!("invalid manifest entry for `{name}`"));
+                };
+                exports.push((export, info));
+            }
+        }
+        Ok(exports)
+    }
+
+    // The import entries of the manifest, which describe the arguments of each #[js_import] function, or None if
+    // the module has no manifest (in which case they are read from the `to_js_import_` exports at runtime)
+    fn manifest_imports(&self) -> Result<Option<Vec<ManifestEntry<'_>>>> {
+        if self.manifest.is_empty() {
+            return Ok(None);
+        }
+        let entries = self.manifest_entries()?.into_iter();
+        Ok(Some(
+            entries
+                .filter(|entry| entry.kind == MANIFEST_IMPORT)
+                .collect(),
+        ))
+    }
+
+    // Returns a copy of the module with the `_info_` exports of #[js] functions removed from the export section
+    fn strip_info_exports(&self, bytes: &[u8]) -> Vec<u8> {
+        let Some(section) = self.export_section.clone() else {
+            return bytes.to_vec();
+        };
+        let stripped: Vec<&str> = self
+            .js_exports()
+            .map(|(_, info)| info.name.as_str())
+            .collect();
+        let exports: Vec<&Export> = self
+            .exports
+            .iter()
+            .filter(|d| !stripped.contains(&d.name.as_str()))
+            .collect();
+
+        let mut contents = Vec::new();
+        write_u32(&mut contents, exports.len() as u32);
+        for export in exports {
+            write_u32(&mut contents, export.name.len() as u32);
+            contents.extend_from_slice(export.name.as_bytes());
+            contents.push(export.kind);
+            write_u32(&mut contents, export.index);
+        }
+
+        let mut out = bytes[..section.start].to_vec();
+        out.push(7);
+        write_u32(&mut out, contents.len() as u32);
+        out.extend(contents);
+        out.extend_from_slice(&bytes[section.end..]);
+        out
+    }
+}
+
+fn write_u32(out: &mut Vec<u8>, mut x: u32) {
+    loop {
+        let b = (x & 0x7f) as u8;
+        x >>= 7;
+        if x == 0 {
+            out.push(b);
+            return;
+        }
+        out.push(b | 0x80);
+    }
+}
+
+// Statically evaluates the body of an `_info_` function, which is expected to return a constant:
+// no locals, followed by an f64.const instruction and the end of the function.
+fn evaluate_info(body: &[u8]) -> Option<[u8; 8]> {
+    match body {
+        [0, 0x44, value @ .., 0x0b] => value.try_into().ok(),
+        _ => None,
+    }
+}
+
+// Code generation
+//
+
+// The JavaScript glue code, which declares (but does not export) `wrap`, `createClass`, and `wrapWorker`,
+// without the code that generates wrappers at runtime (see build.rs)
+const LIB_JS: &str = include_str!(concat!(env!("OUT_DIR"), "/lib.precompiled.js"));
+
+// In enum variant order (enum: ArrayType)
+const ARRAY_TYPES: [&str; 10] = [
+    "Uint8Array",
+    "Int8Array",
+    "Uint16Array",
+    "Int16Array",
+    "Uint32Array",
+    "Int32Array",
+    "Float32Array",
+    "BigUint64Array",
+    "BigInt64Array",
+    "Float64Array",
+];
+
+// The parameters of the functions returned by wrapper_source (see `wrapperParams` in lib.js)
+const WRAPPER_PARAMS: &str = "exports, tryResult, tryOption, transform, u32Pair, arrayType, promise, afterCall, arg, callbacks, trackView, pinned, slice, track";
+
+fn generate(module: &Module, bytes: &[u8], input: &str) -> Result<String> {
+    let mut js = String::new();
+    writeln!(js, "// Generated by to_js from {input}. Do not edit.").unwrap();
+    js.push_str(LIB_JS);
+    js.push_str("\nconst precompiled = {\n\texports: {\n");
+    for (export, type_info) in module.type_infos(bytes)? {
+        let num_args = module
+            .num_params(export.index)
+            .ok_or_else(|| format!("`{}` is not defined in the module", export.name))?;
+        // The wrapper is a function named after the export
+        if !is_identifier(&export.name) {
+            return Err(format!(
+                "`{}` is not a valid JavaScript identifier, so it cannot be wrapped",
+                export.name
+            ));
+        }
+        let source = wrapper_source(&export.name, type_info, num_args);
+        writeln!(
+            js,
+            "\t\t{}: [{:?}, function ({WRAPPER_PARAMS}) {{{source}\n\t\t}}],",
+            json_string(&export.name),
+            type_info
+        )
+        .unwrap();
+    }
+    js.push_str("\t},\n\timports: ");
+    match module.manifest_imports()? {
+        Some(imports) => {
+            js.push_str("{\n");
+            for ManifestEntry { name, infos, .. } in imports {
+                writeln!(js, "\t\t{}: {infos:?},", json_string(name)).unwrap();
+            }
+            js.push_str("\t},\n");
+        }
+        None => js.push_str("null,\n"),
+    }
+    js.push_str(
+        "};
+
+// Wrap an instance of the module using the precompiled wrappers. The arguments are the same as those of `wrap`.
+function wrapPrecompiled(instance, alwaysCopyData, options = {}) {
+\treturn wrap(instance, alwaysCopyData, { ...options, precompiled });
+}
+
+// Run a module in a worker using the precompiled wrappers, whose declaration is sent to the worker as source
+function wrapWorkerPrecompiled(module) {
+\tconst exports = Object.entries(precompiled.exports).map(
+\t\t([name, [typeInfo, fn]]) => `${JSON.stringify(name)}: [${JSON.stringify(typeInfo)}, ${fn}]`
+\t);
+\tconst imports = JSON.stringify(precompiled.imports);
+\treturn wrapWorker(module, `const precompiled = { exports: { ${exports.join(\", \")} }, imports: ${imports} };`);
+}
+
+export { wrapPrecompiled as wrap, createClass, wrapWorkerPrecompiled as wrapWorker };
+",
+    );
+    Ok(js)
+}
+
+// The Python decoder, which refers to (but does not define) the type info table `INFO`
+const HOST_PY: &str = include_str!("../host.py");
+
+fn generate_python(module: &Module, bytes: &[u8], input: &str) -> Result<String> {
+    let mut py = String::new();
+    writeln!(py, "# Generated by to_js from {input}. Do not edit.").unwrap();
+    py.push_str(HOST_PY);
+    py.push_str("\n\n# Type info of each #[js] function, as the 8 bytes of its Info\nINFO = {\n");
+    for (export, type_info) in module.type_infos(bytes)? {
+        // JSON strings are also Python string literals
+        writeln!(
+            py,
+            "    {}: bytes({:?}),",
+            json_string(&export.name),
+            type_info
+        )
+        .unwrap();
+    }
+    py.push_str("}\n");
+    Ok(py)
+}
+
+// Words that cannot be used as the name of a function in strict mode code, which includes the generated ES module
+const RESERVED_WORDS: &[&str] = &[
+    "arguments",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "function",
+    "if",
+    "implements",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+// Whether a name can be used as the name of a JavaScript function. Export names are usually Rust identifiers, so
+// alphabetic and alphanumeric characters stand in for JavaScript's ID_Start and ID_Continue (which `isIdentifier` in
+// lib.js checks exactly).
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+        && !RESERVED_WORDS.contains(&name)
+}
+
+// Quotes a string as a JSON string literal, which is also a JavaScript string literal
+fn json_string(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            // Control characters, and the line and paragraph separators, which older engines reject in strings
+            c if c.is_control() || c == '\u{2028}' || c == '\u{2029}' => {
+                write!(out, "\\u{:04x}", c as u32).unwrap()
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// Returns the source of a function that returns a wrapper for an export, whose name must be a valid identifier (see
+// is_identifier) since the wrapper is named after it.
+// Note: This mirrors `wrapperSource` in lib.js, and the two should be kept in sync.
+fn wrapper_source(name: &str, type_info: [u8; 8], num_args: usize) -> String {
+    let [is_result, is_option, is_array, array_type, transform, is_promise, is_pinned, converts_args] =
+        type_info.map(|x| x as usize);
+    let (is_result, is_option, is_array) = (is_result != 0, is_option != 0, is_array != 0);
+    let (is_promise, is_pinned, converts_args) =
+        (is_promise != 0, is_pinned != 0, converts_args != 0);
+    let args: Vec<String> = (1..=num_args).map(|i| format!("x{i}")).collect();
+    let needs_pair = is_result || is_option || is_array;
+    let is_view = is_array && transform == 9;
+    let may_copy = transform < 7 || is_view;
+    let result = if may_copy {
+        "(slice ? ret.slice() : ret)"
+    } else {
+        "ret"
+    };
+
+    let mut s = String::new();
+    s.push_str("\n\t\tfunction decode(value) {");
+    if needs_pair {
+        s.push_str("\n\t\t\tlet pair = u32Pair(value);");
+    }
+    if is_result {
+        s.push_str("\n\t\t\ttryResult(pair);");
+    }
+    if is_option {
+        s.push_str("\n\t\t\tif (tryOption(pair)) return null;");
+    }
+    if is_pinned {
+        s.push_str("\n\t\t\tconst handle = value;");
+        s.push_str("\n\t\t\tvalue = exports.to_js_pinned_value(handle);");
+    }
+    if is_pinned && is_array {
+        s.push_str("\n\t\t\tpair = u32Pair(value);");
+    }
+    if is_array {
+        let array_type = ARRAY_TYPES.get(array_type).unwrap_or(&"Uint8Array");
+        write!(
+            s,
+            "\n\t\t\tvalue = new {array_type}(exports.memory.buffer, pair[0], pair[1]);"
+        )
+        .unwrap();
+    }
+    s.push_str("\n\t\t\tconst ret = transform(value, arrayType);");
+    if is_view {
+        s.push_str("\n\t\t\tif (track) trackView(ret);");
+    }
+    if is_pinned {
+        write!(s, "\n\t\t\treturn pinned({result}, handle);").unwrap();
+    } else {
+        write!(s, "\n\t\t\treturn {result};").unwrap();
+    }
+    s.push_str("\n\t\t}");
+
+    let n = args.len();
+    write!(s, "\n\t\treturn function {name}({}) {{", args.join(", ")).unwrap();
+    write!(
+        s,
+        "\n\t\t\tif (arguments.length !== {n}) {{\
+         \n\t\t\t\tthrow new Error(`{name}: expected {n} argument{}, got ${{arguments.length}}`);\
+         \n\t\t\t}}",
+        if n == 1 { "" } else { "s" }
+    )
+    .unwrap();
+    let call = format!(
+        "exports[{}]({})",
+        json_string(name),
+        args.iter()
+            .map(|x| if converts_args {
+                format!("arg({x})")
+            } else {
+                x.clone()
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let decode = if is_promise {
+        "promise(value, decode)"
+    } else {
+        "decode(value)"
+    };
+    if converts_args {
+        write!(
+            s,
+            "\n\t\t\tconst numCallbacks = callbacks.length;\
+             \n\t\t\ttry {{\
+             \n\t\t\t\tconst value = {call};\
+             \n\t\t\t\tafterCall();\
+             \n\t\t\t\treturn {decode};\
+             \n\t\t\t}} finally {{\
+             \n\t\t\t\tcallbacks.length = numCallbacks;\
+             \n\t\t\t}}"
+        )
+        .unwrap();
+    } else {
+        write!(
+            s,
+            "\n\t\t\tconst value = {call};\
+             \n\t\t\tafterCall();\
+             \n\t\t\treturn {decode};"
+        )
+        .unwrap();
+    }
+    s.push_str("\n\t\t};");
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write as _;
+    use std::process::{Command, Stdio};
+
+    // Compares the code generated by `wrapperSource` in lib.js (evaluated with node) with that generated by
+    // wrapper_source, ignoring whitespace and semicolons
+    const COMPARE: &str = r#"
+        const { readFileSync } = require("node:fs");
+        const wrapperSource = new Function(`${readFileSync(process.env.LIB_JS, "utf8")}\nreturn wrapperSource;`)();
+        const arrayTypes = [Uint8Array, Int8Array, Uint16Array, Int16Array, Uint32Array, Int32Array, Float32Array,
+            BigUint64Array, BigInt64Array, Float64Array];
+        const normalize = (source) => source.replace(/[\s;]/g, "");
+        for (const [name, typeInfo, numArgs, source] of JSON.parse(readFileSync(0, "utf8"))) {
+            const expected = wrapperSource(name, typeInfo, numArgs, arrayTypes);
+            if (normalize(expected) !== normalize(source)) {
+                console.log(`${name}, type info ${typeInfo}, ${numArgs} arguments:\nlib.js:${expected}\nto_js.rs:${source}`);
+                process.exit(1);
+            }
+        }
+    "#;
+
+    #[test]
+    fn wrapper_source_matches_lib_js() {
+        // Every combination of type info, as JSON [name, typeInfo, numArgs, source] arrays
+        let mut cases = Vec::new();
+        for flags in 0..64u8 {
+            let [is_result, is_option, is_array, is_promise, is_pinned, converts_args] =
+                [0, 1, 2, 3, 4, 5].map(|bit| flags >> bit & 1);
+            // Arrays always have an element type
+            let array_types = if is_array == 1 { 0..10 } else { 0..11 };
+            for array_type in array_types {
+                for transform in 0..18 {
+                    let type_info = [
+                        is_result,
+                        is_option,
+                        is_array,
+                        array_type,
+                        transform,
+                        is_promise,
+                        is_pinned,
+                        converts_args,
+                    ];
+                    let num_args = cases.len() % 3;
+                    let name = ["f", "$f_1", "café"][cases.len() / 3 % 3];
+                    let source = wrapper_source(name, type_info, num_args);
+                    cases.push(format!(
+                        "[{}, {type_info:?}, {num_args}, {}]",
+                        json_string(name),
+                        json_string(&source)
+                    ));
+                }
+            }
+        }
+
+        let child = Command::new("node")
+            .args(["-e", COMPARE])
+            .env("LIB_JS", concat!(env!("CARGO_MANIFEST_DIR"), "/src/lib.js"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn();
+        let Ok(mut child) = child else {
+            eprintln!("node is not installed; skipping the comparison with lib.js");
+            return;
+        };
+        let mut stdin = child.stdin.take().unwrap();
+        write!(stdin, "[{}]", cases.join(",")).unwrap();
+        drop(stdin);
+        let output = child.wait_with_output().unwrap();
+        assert!(
+            output.status.success(),
+            "wrapper_source differs from wrapperSource in lib.js for {}",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+
+    #[test]
+    fn only_identifiers_name_wrappers() {
+        for name in ["add", "_private", "$", "café", "x1"] {
+            assert!(is_identifier(name), "{name}");
+        }
+        for name in [
+            "", "1x", "a-b", "a b", "a\"b", "new", "delete", "eval", "let",
+        ] {
+            assert!(!is_identifier(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn json_strings_are_escaped() {
+        assert_eq!(json_string("add"), r#""add""#);
+        assert_eq!(json_string("a\"b\\c"), r#""a\"b\\c""#);
+        assert_eq!(json_string("a\nb\u{2028}é"), r#""a\u000ab\u2028é""#);
+    }
+
+    // A module exporting a function with the given name, which is described by its manifest along with an import
+    fn module_exporting(name: &str, import: &str) -> Vec<u8> {
+        let mut manifest = Vec::new();
+        for (kind, name) in [(MANIFEST_EXPORT, name), (MANIFEST_IMPORT, import)] {
+            manifest.push(kind);
+            manifest.extend((name.len() as u32).to_le_bytes());
+            manifest.extend(name.as_bytes());
+            manifest.push(1);
+            manifest.extend([0, 0, 0, 10, 9, 0, 0, 0]);
+        }
+        let escape = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .map(|b| format!("\\{b:02x}"))
+                .collect::<String>()
+        };
+        let wat = format!(
+            r#"(module
+                (func (export "{name}") (result f64) (f64.const 0))
+                (@custom "to_js_manifest" "{manifest}"))"#,
+            name = escape(name.as_bytes()),
+            manifest = escape(&manifest),
+        );
+        wat::parse_str(wat).unwrap()
+    }
+
+    #[test]
+    fn generated_names_are_quoted() {
+        let bytes = module_exporting("add", "log\"it");
+        let js = generate(&Module::parse(&bytes).unwrap(), &bytes, "test.wasm").unwrap();
+        assert!(js.contains("\t\t\"add\": ["));
+        assert!(js.contains("exports[\"add\"]()"));
+        assert!(js.contains("\t\t\"log\\\"it\": ["));
+
+        for name in ["new", "a-b", "x\"); alert(1); (\""] {
+            let bytes = module_exporting(name, "log");
+            let error = generate(&Module::parse(&bytes).unwrap(), &bytes, "test.wasm").unwrap_err();
+            assert!(
+                error.contains("not a valid JavaScript identifier"),
+                "{error}"
+            );
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// Logging to the JavaScript console, via a function imported from the `to_js` namespace that the JavaScript
+// side provides by default. Messages are filtered by a maximum level that can be set from JavaScript with
+// `rs.setLogLevel(level)`, which also sets the maximum level for the `log` crate if the `log` feature is enabled.
+
+/// Log levels, in the same order as `log::LevelFilter` so that levels can be shared with the `log` crate.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+static MAX_LEVEL: AtomicU32 = AtomicU32::new(Level::Trace as u32);
+
+pub fn max_level() -> u32 {
+    MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn enabled(level: Level) -> bool {
+    level != Level::Off && level as u32 <= max_level()
+}
+
+/// Writes a message to the JavaScript console if its level is enabled.
+/// Called by the console_log!, console_warn!, and console_error! macros.
+pub fn log(level: Level, message: &str) {
+    if enabled(level) {
+        write(level, message);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(level: Level, message: &str) {
+    #[link(wasm_import_module = "to_js")]
+    extern "C" {
+        fn to_js_console(level: u32, ptr: *const u8, len: usize);
+    }
+
+    unsafe { to_js_console(level as u32, message.as_ptr(), message.len()) }
+}
+
+// Outside of WebAssembly, log to stderr, or in tests, to a list of messages that they can check
+#[cfg(all(not(target_arch = "wasm32"), not(test)))]
+fn write(_level: Level, message: &str) {
+    eprintln!("{message}");
+}
+
+#[cfg(all(not(target_arch = "wasm32"), test))]
+fn write(level: Level, message: &str) {
+    tests::LOGGED.with_borrow_mut(|logged| logged.push((level, message.to_string())));
+}
+
+#[unsafe(export_name = "to_js_set_log_level")]
+extern "C" fn set_log_level(level: u32) {
+    MAX_LEVEL.store(level.min(Level::Trace as u32), Ordering::Relaxed);
+    #[cfg(feature = "log")]
+    log::set_max_level(logger::level_filter(level));
+}
+
+/// Formats and logs a message to the JavaScript console with `console.log`.
+#[macro_export]
+macro_rules! console_log {
+    ($($arg:tt)*) => {
+        $crate::console::log($crate::console::Level::Info, &format!($($arg)*))
+    };
+}
+
+/// Formats and logs a message to the JavaScript console with `console.warn`.
+#[macro_export]
+macro_rules! console_warn {
+    ($($arg:tt)*) => {
+        $crate::console::log($crate::console::Level::Warn, &format!($($arg)*))
+    };
+}
+
+/// Formats and logs a message to the JavaScript console with `console.error`.
+#[macro_export]
+macro_rules! console_error {
+    ($($arg:tt)*) => {
+        $crate::console::log($crate::console::Level::Error, &format!($($arg)*))
+    };
+}
+
+#[cfg(feature = "log")]
+pub use logger::{init_logger, ConsoleLogger};
+
+#[cfg(feature = "log")]
+mod logger {
+    use super::{Level, MAX_LEVEL};
+    use log::{LevelFilter, Metadata, Record, SetLoggerError};
+    use std::sync::atomic::Ordering;
+
+    /// A `log` backend that writes to the JavaScript console.
+    pub struct ConsoleLogger;
+
+    impl log::Log for ConsoleLogger {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= log::max_level()
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                let level = match record.level() {
+                    log::Level::Error => Level::Error,
+                    log::Level::Warn => Level::Warn,
+                    log::Level::Info => Level::Info,
+                    log::Level::Debug => Level::Debug,
+                    log::Level::Trace => Level::Trace,
+                };
+                super::write(level, &format!("[{}] {}", record.target(), record.args()));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Installs `ConsoleLogger` as the global logger, with the current maximum level.
+    pub fn init_logger() -> Result<(), SetLoggerError> {
+        log::set_logger(&ConsoleLogger)?;
+        log::set_max_level(level_filter(MAX_LEVEL.load(Ordering::Relaxed)));
+        Ok(())
+    }
+
+    pub(super) fn level_filter(level: u32) -> LevelFilter {
+        match level {
+            0 => LevelFilter::Off,
+            1 => LevelFilter::Error,
+            2 => LevelFilter::Warn,
+            3 => LevelFilter::Info,
+            4 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    thread_local! {
+        pub(super) static LOGGED: RefCell<Vec<(Level, String)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn logged() -> Vec<(Level, String)> {
+        LOGGED.take()
+    }
+
+    // The maximum level is global, so it is only changed by this test
+    #[test]
+    fn messages_are_filtered_by_level() {
+        set_log_level(Level::Warn as u32);
+        assert_eq!(max_level(), Level::Warn as u32);
+        assert!(enabled(Level::Error) && enabled(Level::Warn));
+        assert!(!enabled(Level::Info) && !enabled(Level::Off));
+        crate::console_log!("info {}", 1);
+        crate::console_warn!("warn {}", 2);
+        crate::console_error!("error {}", 3);
+        log(Level::Trace, "trace");
+        assert_eq!(
+            logged(),
+            [
+                (Level::Warn, "warn 2".to_string()),
+                (Level::Error, "error 3".to_string())
+            ]
+        );
+
+        #[cfg(feature = "log")]
+        {
+            assert_eq!(log::max_level(), log::LevelFilter::Warn);
+            init_logger().unwrap();
+            log::info!(target: "app", "info");
+            log::warn!(target: "app", "warn");
+            assert_eq!(logged(), [(Level::Warn, "[app] warn".to_string())]);
+        }
+
+        // Levels above Trace are clamped
+        set_log_level(100);
+        assert_eq!(max_level(), Level::Trace as u32);
+        log(Level::Trace, "trace");
+        #[cfg(feature = "log")]
+        {
+            assert_eq!(log::max_level(), log::LevelFilter::Trace);
+            log::debug!(target: "app", "debug");
+        }
+        let mut expected = vec![(Level::Trace, "trace".to_string())];
+        if cfg!(feature = "log") {
+            expected.push((Level::Debug, "[app] debug".to_string()));
+        }
+        assert_eq!(logged(), expected);
+
+        set_log_level(Level::Off as u32);
+        for level in [
+            Level::Error,
+            Level::Warn,
+            Level::Info,
+            Level::Debug,
+            Level::Trace,
+        ] {
+            log(level, "message");
+        }
+        #[cfg(feature = "log")]
+        {
+            assert_eq!(log::max_level(), log::LevelFilter::Off);
+            log::error!("error");
+        }
+        assert!(logged().is_empty());
+
+        set_log_level(Level::Trace as u32);
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn levels_match_the_log_crate() {
+        use log::LevelFilter;
+        let levels = [
+            Level::Off,
+            Level::Error,
+            Level::Warn,
+            Level::Info,
+            Level::Debug,
+            Level::Trace,
+        ];
+        let filters = [
+            LevelFilter::Off,
+            LevelFilter::Error,
+            LevelFilter::Warn,
+            LevelFilter::Info,
+            LevelFilter::Debug,
+            LevelFilter::Trace,
+        ];
+        for (level, filter) in levels.into_iter().zip(filters) {
+            assert_eq!(logger::level_filter(level as u32), filter);
+            assert_eq!(level as usize, filter as usize);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::types::packed::U32Pair;
+use crate::{CallFrame, IntoWasm, ToWasm, Wasm};
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+/// A minimal single-threaded executor for futures returned to JavaScript as promises.
+/// Futures are polled from JavaScript via the `to_js_poll` export, which polls every task that has been
+/// woken since the last poll and returns the handles of the tasks that completed. The results of completed
+/// tasks, which are encoded when the task completes, are then retrieved via `to_js_task_result`.
+/// Since futures need not be Send, tasks are stored in a thread-local rather than a global.
+type Task = Pin<Box<dyn Future<Output = Wasm>>>;
+
+enum Slot {
+    Pending(Task),
+    // The task is currently being polled (its future has been temporarily taken out of the slot)
+    Polling,
+    Complete(Wasm),
+    Free,
+}
+
+thread_local! {
+    static TASKS: RefCell<Vec<Slot>> = const { RefCell::new(Vec::new()) };
+    // The handles of the tasks that completed during the last call to `to_js_poll`, which returns a view of them
+    static COMPLETED: RefCell<Vec<u32>> = const { RefCell::new(Vec::new()) };
+    // Handles of tasks that have been woken and should be polled. Wakers must be Send + Sync, and may be woken
+    // from another thread, so each waker holds a reference to the queue of the thread that owns its task.
+    static WOKEN: Arc<Mutex<Vec<u32>>> = Arc::default();
+}
+
+struct TaskWaker {
+    handle: u32,
+    woken: Arc<Mutex<Vec<u32>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.woken.lock().unwrap().push(self.handle);
+    }
+}
+
+fn take_woken() -> Vec<u32> {
+    WOKEN.with(|woken| std::mem::take(&mut *woken.lock().unwrap()))
+}
+
+/// Registers a future with the executor, returning its handle.
+/// The task is polled for the first time on the next call to `to_js_poll`.
+pub(crate) fn spawn<T: IntoWasm>(future: impl Future<Output = T> + 'static) -> u32 {
+    let task: Task = Box::pin(async move { future.await.into_wasm() });
+    let handle = TASKS.with_borrow_mut(|tasks| {
+        match tasks.iter().position(|slot| matches!(slot, Slot::Free)) {
+            Some(i) => {
+                tasks[i] = Slot::Pending(task);
+                i
+            }
+            None => {
+                tasks.push(Slot::Pending(task));
+                tasks.len() - 1
+            }
+        }
+    }) as u32;
+    WOKEN.with(|woken| woken.lock().unwrap().push(handle));
+    handle
+}
+
+/// Polls woken tasks, returning the handles of those that completed as a view of a u32 array.
+/// Note: This does not clear the keepalive stash, since it is called from a microtask rather than by the caller,
+/// and so would otherwise invalidate the values returned by calls that the caller has yet to use.
+#[unsafe(export_name = "to_js_poll")]
+extern "C" fn poll() -> f64 {
+    let _frame = CallFrame::enter();
+    let mut completed = COMPLETED.take();
+    completed.clear();
+    // Polling a task may wake other tasks, so keep going until no tasks remain woken.
+    loop {
+        let woken = take_woken();
+        if woken.is_empty() {
+            break;
+        }
+        for handle in woken {
+            // Take the future out of its slot so that it can spawn other tasks while it is being polled
+            let slot = TASKS.with_borrow_mut(|tasks| {
+                std::mem::replace(&mut tasks[handle as usize], Slot::Polling)
+            });
+            let Slot::Pending(mut task) = slot else {
+                // Spurious or repeated wakeup of a task that already completed
+                TASKS.with_borrow_mut(|tasks| tasks[handle as usize] = slot);
+                continue;
+            };
+            let woken = WOKEN.with(Arc::clone);
+            let waker = Waker::from(Arc::new(TaskWaker { handle, woken }));
+            let slot = match task.as_mut().poll(&mut Context::from_waker(&waker)) {
+                Poll::Ready(value) => {
+                    completed.push(handle);
+                    Slot::Complete(value)
+                }
+                Poll::Pending => Slot::Pending(task),
+            };
+            TASKS.with_borrow_mut(|tasks| tasks[handle as usize] = slot);
+        }
+    }
+    let wasm = U32Pair([completed.as_ptr() as u32, completed.len() as u32]).to_wasm();
+    COMPLETED.set(completed);
+    wasm.value()
+}
+
+/// Returns the encoded result of a completed task and frees its slot, or NaN (leaving the slot as it is) if there
+/// is no completed task with the handle, since the handle comes from JavaScript. lib.js only asks for the results
+/// of the tasks reported by `to_js_poll`.
+/// Note: This does not clear the keepalive stash either, since it is called after `to_js_poll` in
+/// order to retrieve results that were encoded (and possibly stashed) during that call.
+#[unsafe(export_name = "to_js_task_result")]
+extern "C" fn task_result(handle: u32) -> f64 {
+    TASKS.with_borrow_mut(|tasks| match tasks.get_mut(handle as usize) {
+        Some(slot @ Slot::Complete(_)) => match std::mem::replace(slot, Slot::Free) {
+            Slot::Complete(value) => value.value(),
+            _ => unreachable!(),
+        },
+        _ => f64::NAN,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::keepalive::keepalive_stats;
+    use crate::KeepAlive;
+
+    #[test]
+    fn poll_completes_tasks_without_clearing_the_stash() {
+        // A value returned by a synchronous call, which the caller may still be using
+        let _ = KeepAlive::new(vec![1u8, 2, 3]);
+        let ready = spawn(async { 42u32 });
+        let pending = spawn(std::future::pending::<u32>());
+
+        poll();
+        assert_eq!(
+            COMPLETED.with_borrow(|completed| completed.clone()),
+            [ready]
+        );
+        assert_eq!(keepalive_stats().len, 1);
+        assert_eq!(task_result(ready), 42.0);
+
+        // Completed handles are not reported again, and the slot of the completed task is reused
+        poll();
+        assert!(COMPLETED.with_borrow(Vec::is_empty));
+        assert_eq!(spawn(async { 0u32 }), ready);
+        assert_ne!(ready, pending);
+    }
+
+    #[test]
+    fn results_of_unknown_or_pending_tasks_are_nan() {
+        let pending = spawn(std::future::pending::<u32>());
+        poll();
+        assert!(task_result(pending).is_nan());
+        assert!(task_result(u32::MAX).is_nan());
+        // The pending task is left as it is
+        assert!(TASKS.with_borrow(|tasks| matches!(tasks[pending as usize], Slot::Pending(_))));
+    }
+
+    // A future that completes once it has been woken, after storing its waker for the test to wake it
+    struct Woken {
+        waker: Arc<Mutex<Option<Waker>>>,
+        woken: bool,
+    }
+
+    impl Future for Woken {
+        type Output = u32;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<u32> {
+            if self.woken {
+                return Poll::Ready(1);
+            }
+            self.woken = true;
+            *self.waker.lock().unwrap() = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn wakeups_are_queued_for_the_thread_that_owns_the_task() {
+        let waker = Arc::new(Mutex::new(None));
+        let (handle, woken) = std::thread::scope(|scope| {
+            let (tx, rx) = std::sync::mpsc::channel();
+            let stored = waker.clone();
+            let owner = scope.spawn(move || {
+                let handle = spawn(Woken {
+                    waker: stored,
+                    woken: false,
+                });
+                poll();
+                tx.send(handle).unwrap();
+                // Wait for the task to be woken from the other thread, then poll it to completion
+                std::thread::park();
+                poll();
+                (handle, COMPLETED.with_borrow(|completed| completed.clone()))
+            });
+            let handle = rx.recv().unwrap();
+            let waker: Waker = waker.lock().unwrap().take().unwrap();
+            waker.wake();
+            // The wakeup is not queued for this thread, which has no such task
+            assert!(!take_woken().contains(&handle));
+            owner.thread().unpark();
+            owner.join().unwrap()
+        });
+        assert_eq!(woken, [handle]);
+    }
+}
@@ -0,0 +1,202 @@
+# Decodes values returned from a WebAssembly module built with to_js, for calling its #[js] exports from Python.
+# This mirrors `wrap` in lib.js (and the Rust host in src/host) for the encoding described under "ABI" in the
+# README. The `to_js` binary writes this file out together with the type info of each of a module's exports
+# (`INFO`, below), so it does not rely on the `_info_` exports.
+#
+# Values are decoded as follows:
+# - numbers as int (or float, for f32 and f64), and u64/i64 as int
+# - typed arrays (slices, vectors, packed arrays, ViewRefs, and the elements of JsIters) as numpy arrays,
+#   or as array.array if numpy is not installed; data is always copied out of WebAssembly memory
+# - strings as str, Json values as the result of json.loads, and MsgPack and Structured values as bytes
+# - None as None, while an Err raises RustError with the error message
+# Functions that return promises or take callbacks, and #[js_import] functions, are not supported.
+#
+# Usage (with wasmtime):
+#
+#     store = wasmtime.Store()
+#     instance = wasmtime.Instance(store, wasmtime.Module.from_file(store.engine, "my_crate.wasm"), [])
+#     rs = Module(WasmtimeInstance(store, instance))
+#     rs.add(2, 2)  # => 4
+#
+# Other runtimes can be used by passing an object with the same methods as WasmtimeInstance.
+
+import array
+import json
+import struct
+
+try:
+    import numpy
+except ImportError:
+    numpy = None
+
+ABI_VERSION = 2
+
+# In enum variant order (enum: ArrayType), as (numpy dtype, array.array typecode, size in bytes)
+ARRAY_TYPES = [
+    ("<u1", "B", 1),
+    ("<i1", "b", 1),
+    ("<u2", "H", 2),
+    ("<i2", "h", 2),
+    ("<u4", "I", 4),
+    ("<i4", "i", 4),
+    ("<f4", "f", 4),
+    ("<u8", "Q", 8),
+    ("<i8", "q", 8),
+    ("<f8", "d", 8),
+    # ArrayType::None, whose elements are bytes (as in the Rust host)
+    ("<u1", "B", 1),
+]
+ARRAY_TYPE_F32, ARRAY_TYPE_F64 = 6, 9
+
+# In enum variant order (enum: Transform)
+(
+    U8_OCTET, I8_OCTET, U16_QUARTET, I16_QUARTET, U32_PAIR, I32_PAIR, F32_PAIR, AS_U64, AS_I64, IDENTITY,
+    VOID, BOOL, STRING, JSON, MSGPACK, STRUCTURED, ITER, VIEW_REF,
+) = range(18)
+
+HIGH_BITS_NAN = 0xFFF80000
+
+
+class RustError(Exception):
+    """Raised when a function returns an Err, with its error message."""
+
+
+class WasmtimeInstance:
+    """Calls the exports of a wasmtime instance and reads its memory."""
+
+    def __init__(self, store, instance):
+        self.store = store
+        self.exports = instance.exports(store)
+        self.memory = self.exports["memory"]
+
+    def call(self, name, *args):
+        """Calls an export, raising KeyError if there is none with that name."""
+        return self.exports[name](self.store, *args)
+
+    def read(self, ptr, length):
+        """Reads memory, returning fewer bytes than requested at the end of memory."""
+        end = min(ptr + length, self.memory.data_len(self.store))
+        return bytes(self.memory.read(self.store, ptr, end))
+
+
+def typed_array(array_type, data):
+    dtype, typecode, _ = ARRAY_TYPES[array_type]
+    if numpy is not None:
+        return numpy.frombuffer(data, dtype=dtype).copy()
+    result = array.array(typecode, data)
+    # array.array uses native byte order, while WebAssembly is little-endian
+    if struct.pack("=H", 1) != struct.pack("<H", 1):
+        result.byteswap()
+    return result
+
+
+def u32_pair(value):
+    return struct.unpack("<II", struct.pack("<d", value))
+
+
+class Module:
+    """The #[js] exports of an instance, which are available as methods."""
+
+    def __init__(self, instance, info=None):
+        self.instance = instance
+        self.info = INFO if info is None else info
+        try:
+            version = instance.call("to_js_abi_version")
+        except KeyError:
+            version = None
+        if version != ABI_VERSION:
+            raise RuntimeError(
+                f"to_js ABI version mismatch: this decoder expects version {ABI_VERSION}, but the module "
+                + ("does not export one (it may predate versioning)" if version is None else f"uses version {version}")
+            )
+
+    def __getattr__(self, name):
+        if name not in self.__dict__.get("info", {}):
+            raise AttributeError(f"no #[js] function named {name!r}")
+        return lambda *args: self.call(name, *args)
+
+    def __dir__(self):
+        return list(self.info)
+
+    def call(self, name, *args):
+        """Calls a #[js] function, returning its decoded value."""
+        return self.decode(self.instance.call(name, *args), self.info[name])
+
+    def decode(self, value, info):
+        is_result, is_option, is_array, array_type, transform, is_promise, is_pinned, _ = info
+        if is_promise:
+            raise NotImplementedError("promises cannot be decoded in Python")
+
+        # Check for Err and None, which are encoded in the niche of the value
+        low, high = u32_pair(value)
+        if is_array and not is_pinned:
+            # Low bits one
+            if is_result and low == 1 and high != 0:
+                raise RustError(self.c_string(high))
+            if is_option and low == 1 and high == 0:
+                return None
+        else:
+            # High bits NaN
+            if is_result and high == HIGH_BITS_NAN and low != 0:
+                raise RustError(self.c_string(low))
+            if is_option and high == HIGH_BITS_NAN and low == 0:
+                return None
+
+        # Pinned values are copied, so they can be released immediately
+        if is_pinned:
+            handle = int(value)
+            try:
+                return self.transform(self.instance.call("to_js_pinned_value", handle), is_array, array_type, transform)
+            finally:
+                self.instance.call("to_js_release", handle)
+        return self.transform(value, is_array, array_type, transform)
+
+    def transform(self, value, is_array, array_type, transform):
+        if is_array:
+            ptr, length = u32_pair(value)
+            data = self.instance.read(ptr, length * ARRAY_TYPES[array_type][2])
+            if transform == STRING:
+                return data.decode("utf-8")
+            if transform == JSON:
+                return json.loads(data)
+            if transform in (MSGPACK, STRUCTURED):
+                return data
+            return typed_array(array_type, data)
+        if transform <= F32_PAIR:
+            # Packed arrays use the same index for their transform and element type
+            return typed_array(transform, struct.pack("<d", value))
+        if transform == AS_U64:
+            return struct.unpack("<Q", struct.pack("<d", value))[0]
+        if transform == AS_I64:
+            return struct.unpack("<q", struct.pack("<d", value))[0]
+        if transform == IDENTITY:
+            return value if array_type in (ARRAY_TYPE_F32, ARRAY_TYPE_F64) else int(value)
+        if transform == VOID:
+            return None
+        if transform == BOOL:
+            return bool(value)
+        if transform == ITER:
+            return self.collect(int(value), array_type)
+        raise NotImplementedError(f"values with transform {transform} cannot be decoded in Python")
+
+    def collect(self, handle, array_type):
+        """Collects the remaining elements of a JsIter, pulling chunks until an empty one."""
+        data = b""
+        try:
+            while True:
+                ptr, length = u32_pair(self.instance.call("to_js_iter_next", handle))
+                if length == 0:
+                    return typed_array(array_type, data)
+                data += self.instance.read(ptr, length * ARRAY_TYPES[array_type][2])
+        finally:
+            self.instance.call("to_js_iter_drop", handle)
+
+    def c_string(self, ptr):
+        """Reads a NUL-terminated error message."""
+        data = b""
+        while b"\0" not in data:
+            chunk = self.instance.read(ptr + len(data), 64)
+            if not chunk:
+                break
+            data += chunk
+        return data.split(b"\0", 1)[0].decode("utf-8", "replace")
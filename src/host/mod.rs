@@ -0,0 +1,736 @@
+// Host-side decoding of values returned from a module built with to_js, for calling its #[js] exports from a
+// native Rust program (eg. a server running the same .wasm module as the browser). This is the equivalent of
+// `wrap` in lib.js: `Host` reads the type info of the exported functions from the module's manifest (see
+// manifest.rs), or from their `_info_` exports if it has none, calls them through a WebAssembly runtime, and
+// decodes the returned values into `JsValue`s, copying data out of WebAssembly memory.
+//
+// The runtime is abstracted by the `Instance` trait, which is implemented for wasmi with the `wasmi` feature.
+// Functions that return promises and the arguments of #[js_import] functions are not supported, and arguments
+// are passed as numbers, as from JavaScript.
+
+#[cfg(feature = "wasmi")]
+mod wasmi;
+
+#[cfg(feature = "wasmi")]
+pub use self::wasmi::WasmiInstance;
+
+use crate::typeinfo::{ArrayType, Transform, ABI_VERSION};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// An instantiated WebAssembly module, as seen by `Host`.
+pub trait Instance {
+    /// The runtime's error type, eg. for traps
+    type Error;
+
+    /// Returns the names of the exported functions.
+    fn functions(&self) -> Vec<String>;
+
+    /// Calls an exported function. As in JavaScript, arguments are converted to the types of the function's
+    /// parameters (integers are truncated and wrap around), and its result is converted to a number.
+    /// Functions that do not return a value return 0.
+    fn call(&mut self, name: &str, args: &[f64]) -> Result<f64, Self::Error>;
+
+    /// Returns the contents of the exported memory.
+    fn memory(&self) -> &[u8];
+}
+
+/// A value returned from a `#[js]` function, decoded in the same way as by `wrap` in JavaScript.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsValue {
+    Undefined,
+    /// `None`
+    Null,
+    Bool(bool),
+    Number(f64),
+    /// A 64-bit integer, signed or unsigned
+    BigInt(i128),
+    String(String),
+    /// A slice, vector, packed array, `ViewRef`, or the elements of a `JsIter`
+    TypedArray(TypedArray),
+    /// The JSON text of a `Json` value
+    Json(String),
+    /// The encoded bytes of a `MsgPack` value
+    MsgPack(Vec<u8>),
+    /// An array decoded from a `Structured` value (see ser.rs)
+    Array(Vec<JsValue>),
+    /// An object decoded from a `Structured` value, as its keys (converted to strings, as in JavaScript) and values
+    /// in serialization order
+    Object(Vec<(String, JsValue)>),
+}
+
+/// The elements of a typed array, by array type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypedArray {
+    U8(Vec<u8>),
+    I8(Vec<i8>),
+    U16(Vec<u16>),
+    I16(Vec<i16>),
+    U32(Vec<u32>),
+    I32(Vec<i32>),
+    F32(Vec<f32>),
+    U64(Vec<u64>),
+    I64(Vec<i64>),
+    F64(Vec<f64>),
+}
+
+#[derive(Debug)]
+pub enum Error<E> {
+    /// An error from the WebAssembly runtime, such as a trap
+    Runtime(E),
+    /// The function returned an `Err` with this message
+    Err(String),
+    /// There is no `#[js]` function with this name
+    NotFound(String),
+    /// The module's ABI version (if it has one) differs from `ABI_VERSION`
+    AbiVersion(Option<u32>),
+    /// The value cannot be decoded on the host, eg. because it is a promise
+    Unsupported(&'static str),
+    /// The value refers to memory outside of the WebAssembly memory
+    OutOfBounds,
+    /// The module (or its manifest) is malformed
+    InvalidModule(&'static str),
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Runtime(e) => write!(f, "{e}"),
+            Error::Err(message) => write!(f, "{message}"),
+            Error::NotFound(name) => write!(f, "no #[js] function named `{name}`"),
+            Error::AbiVersion(Some(version)) => write!(
+                f,
+                "to_js ABI version mismatch: expected version {ABI_VERSION}, but the module uses version {version}"
+            ),
+            Error::AbiVersion(None) => write!(
+                f,
+                "to_js ABI version mismatch: expected version {ABI_VERSION}, but the module does not export one"
+            ),
+            Error::Unsupported(what) => write!(f, "{what} cannot be decoded on the host"),
+            Error::OutOfBounds => write!(f, "value refers to memory outside of the WebAssembly memory"),
+            Error::InvalidModule(what) => write!(f, "invalid module: {what}"),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for Error<E> {}
+
+/// The `#[js]` exports of an instance, which can be called by name.
+pub struct Host<I> {
+    instance: I,
+    /// Type info of each `#[js]` function, as the 8 bytes of an `Info`
+    infos: BTreeMap<String, [u8; 8]>,
+}
+
+impl<I: Instance> Host<I> {
+    /// Checks that the module uses the same ABI version as this crate, and reads the type info of its `#[js]` functions
+    /// from the manifest of the module (whose bytes are `wasm`), or from their `_info_` exports if it has none.
+    pub fn new(mut instance: I, wasm: &[u8]) -> Result<Self, Error<I::Error>> {
+        let functions = instance.functions();
+        if !functions.iter().any(|name| name == "to_js_abi_version") {
+            return Err(Error::AbiVersion(None));
+        }
+        let version = instance
+            .call("to_js_abi_version", &[])
+            .map_err(Error::Runtime)? as u32;
+        if version != ABI_VERSION {
+            return Err(Error::AbiVersion(Some(version)));
+        }
+
+        if let Some(infos) = read_manifest(wasm).map_err(Error::InvalidModule)? {
+            return Ok(Host { instance, infos });
+        }
+        if functions.iter().any(|name| name == "to_js_no_info_exports") {
+            return Err(Error::InvalidModule(
+                "the module has neither a manifest nor `_info_` exports",
+            ));
+        }
+        let mut infos = BTreeMap::new();
+        for export in &functions {
+            if let Some(name) = export.strip_suffix("_info_") {
+                let info = instance.call(export, &[]).map_err(Error::Runtime)?;
+                infos.insert(name.to_string(), info.to_le_bytes());
+            }
+        }
+        Ok(Host { instance, infos })
+    }
+
+    /// Returns the names of the `#[js]` functions.
+    pub fn functions(&self) -> impl Iterator<Item = &str> {
+        self.infos.keys().map(String::as_str)
+    }
+
+    pub fn instance(&mut self) -> &mut I {
+        &mut self.instance
+    }
+
+    /// Calls a `#[js]` function, returning its decoded value. A returned `Err` is converted to `Error::Err`.
+    pub fn call(&mut self, name: &str, args: &[f64]) -> Result<JsValue, Error<I::Error>> {
+        let info = *self
+            .infos
+            .get(name)
+            .ok_or_else(|| Error::NotFound(name.to_string()))?;
+        let value = self.instance.call(name, args).map_err(Error::Runtime)?;
+        self.decode(value, info)
+    }
+
+    // Decodes a value according to its type info (see "ABI" in the README and `decoder` in lib.js)
+    fn decode(&mut self, mut value: f64, info: [u8; 8]) -> Result<JsValue, Error<I::Error>> {
+        let [is_result, is_option, is_array, _, _, is_promise, is_pinned, _] = info.map(|x| x != 0);
+        let [_, _, _, array_type, transform, ..] = info;
+        if is_promise {
+            return Err(Error::Unsupported("a promise"));
+        }
+
+        // Check for `Err` and `None`, which are encoded in the niche of the value
+        let [low, high] = u32_pair(value);
+        if is_array && !is_pinned {
+            // Low bits one
+            if is_result && low == 1 && high != 0 {
+                return Err(Error::Err(self.c_string(high)?));
+            }
+            if is_option && low == 1 && high == 0 {
+                return Ok(JsValue::Null);
+            }
+        } else {
+            // High bits NaN
+            if is_result && high == 0xfff80000 && low != 0 {
+                return Err(Error::Err(self.c_string(low)?));
+            }
+            if is_option && high == 0xfff80000 && low == 0 {
+                return Ok(JsValue::Null);
+            }
+        }
+
+        // Pinned values are copied, so they can be released immediately
+        if is_pinned {
+            let handle = value;
+            value = self
+                .instance
+                .call("to_js_pinned_value", &[handle])
+                .map_err(Error::Runtime)?;
+            let decoded = self.transform(value, is_array, array_type, transform);
+            self.instance
+                .call("to_js_release", &[handle])
+                .map_err(Error::Runtime)?;
+            return decoded;
+        }
+        self.transform(value, is_array, array_type, transform)
+    }
+
+    fn transform(
+        &mut self,
+        value: f64,
+        is_array: bool,
+        array_type: u8,
+        transform: u8,
+    ) -> Result<JsValue, Error<I::Error>> {
+        let array_type = ArrayType::from_index(array_type);
+        let Some(transform) = Transform::from_index(transform) else {
+            return Err(Error::Unsupported("a value with an unknown transform"));
+        };
+        if is_array {
+            let array_type = array_type.ok_or(Error::Unsupported("an array of unknown type"))?;
+            let [ptr, len] = u32_pair(value);
+            let bytes = self.bytes(ptr, len as usize * array_type.size())?;
+            let text = || String::from_utf8_lossy(bytes).into_owned();
+            return Ok(match transform {
+                Transform::String => JsValue::String(text()),
+                Transform::Json => JsValue::Json(text()),
+                Transform::MsgPack => JsValue::MsgPack(bytes.to_vec()),
+                Transform::Structured => {
+                    let memory = self.instance.memory();
+                    StructuredReader {
+                        bytes,
+                        memory,
+                        pos: 0,
+                    }
+                    .decode()?
+                }
+                Transform::Identity | Transform::ViewRef => {
+                    JsValue::TypedArray(TypedArray::from_bytes(array_type, bytes))
+                }
+                _ => return Err(Error::Unsupported("an array with this transform")),
+            });
+        }
+        Ok(match transform {
+            Transform::U8Octet
+            | Transform::I8Octet
+            | Transform::U16Quartet
+            | Transform::I16Quartet
+            | Transform::U32Pair
+            | Transform::I32Pair
+            | Transform::F32Pair => {
+                // Packed arrays use the same index for their transform and element type
+                let array_type = ArrayType::from_index(transform as u8).unwrap();
+                JsValue::TypedArray(TypedArray::from_bytes(array_type, &value.to_le_bytes()))
+            }
+            Transform::AsU64 => JsValue::BigInt(value.to_bits() as i128),
+            Transform::AsI64 => JsValue::BigInt(value.to_bits() as i64 as i128),
+            Transform::Identity => JsValue::Number(value),
+            Transform::Void => JsValue::Undefined,
+            Transform::Bool => JsValue::Bool(value != 0.0),
+            Transform::Iter => {
+                let array_type =
+                    array_type.ok_or(Error::Unsupported("an iterator of unknown type"))?;
+                self.collect(value, array_type)?
+            }
+            _ => return Err(Error::Unsupported("a value with this transform")),
+        })
+    }
+
+    // Collects the remaining elements of a JsIter into a typed array, pulling chunks until an empty one
+    fn collect(&mut self, handle: f64, array_type: ArrayType) -> Result<JsValue, Error<I::Error>> {
+        let mut bytes = Vec::new();
+        let result = loop {
+            let chunk = match self.instance.call("to_js_iter_next", &[handle]) {
+                Ok(chunk) => chunk,
+                Err(e) => break Err(Error::Runtime(e)),
+            };
+            let [ptr, len] = u32_pair(chunk);
+            if len == 0 {
+                break Ok(());
+            }
+            match self.bytes(ptr, len as usize * array_type.size()) {
+                Ok(chunk) => bytes.extend_from_slice(chunk),
+                Err(e) => break Err(e),
+            }
+        };
+        self.instance
+            .call("to_js_iter_drop", &[handle])
+            .map_err(Error::Runtime)?;
+        result.map(|()| JsValue::TypedArray(TypedArray::from_bytes(array_type, &bytes)))
+    }
+
+    fn bytes(&self, ptr: u32, len: usize) -> Result<&[u8], Error<I::Error>> {
+        let start = ptr as usize;
+        self.instance
+            .memory()
+            .get(start..start + len)
+            .ok_or(Error::OutOfBounds)
+    }
+
+    // Reads a NUL-terminated error message
+    fn c_string(&self, ptr: u32) -> Result<String, Error<I::Error>> {
+        let bytes = self
+            .instance
+            .memory()
+            .get(ptr as usize..)
+            .ok_or(Error::OutOfBounds)?;
+        let end = bytes
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or(Error::OutOfBounds)?;
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
+
+// Reads the type info of the #[js] functions from the entries of the `to_js_manifest` custom section(s) of a module
+// (see manifest.rs), returning None if it has none
+fn read_manifest(wasm: &[u8]) -> Result<Option<BTreeMap<String, [u8; 8]>>, &'static str> {
+    if wasm.get(0..8) != Some(b"\0asm\x01\0\0\0") {
+        return Err("not a WebAssembly module");
+    }
+    let mut sections = Vec::new();
+    let mut r = Reader {
+        bytes: wasm,
+        pos: 8,
+    };
+    while r.pos < wasm.len() {
+        let id = r.byte()?;
+        let len = r.leb()? as usize;
+        let section = r.take(len)?;
+        if id == 0 {
+            let mut r = Reader {
+                bytes: section,
+                pos: 0,
+            };
+            let name_len = r.leb()? as usize;
+            if r.take(name_len)? == b"to_js_manifest" {
+                sections.push(&section[r.pos..]);
+            }
+        }
+    }
+    if sections.is_empty() {
+        return Ok(None);
+    }
+
+    let mut infos = BTreeMap::new();
+    for section in sections {
+        let mut r = Reader {
+            bytes: section,
+            pos: 0,
+        };
+        while r.pos < section.len() {
+            let kind = r.byte()?;
+            let name_len = r.u32()? as usize;
+            let name =
+                std::str::from_utf8(r.take(name_len)?).map_err(|_| "invalid UTF-8 in manifest")?;
+            let count = r.byte()? as usize;
+            let entry = r.take(8 * count)?;
+            if kind == crate::manifest::EXPORT {
+                let info = entry.try_into().map_err(|_| "invalid manifest entry")?;
+                infos.insert(name.to_string(), info);
+            }
+        }
+    }
+    Ok(Some(infos))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or("unexpected end of data")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, &'static str> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, &'static str> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    // Unsigned LEB128
+    fn leb(&mut self) -> Result<u32, &'static str> {
+        let mut result = 0u64;
+        for shift in (0..35).step_by(7) {
+            let b = self.byte()?;
+            result |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return u32::try_from(result).map_err(|_| "integer too large");
+            }
+        }
+        Err("invalid LEB128 integer")
+    }
+}
+
+// Decodes a `Structured` value: an 8-byte header holding the lengths of a data section and an op stream, followed
+// by both (see ser.rs and `decodeStructured` in lib.js). Typed arrays are copied out of the data section, or out of
+// WebAssembly memory if they were serialized with `as_view`.
+struct StructuredReader<'a> {
+    bytes: &'a [u8],
+    memory: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StructuredReader<'a> {
+    fn decode<E>(mut self) -> Result<JsValue, Error<E>> {
+        let data_len = self.reader().u32().map_err(|_| Error::OutOfBounds)?;
+        self.pos = 8 + data_len as usize;
+        self.read()
+    }
+
+    fn reader(&self) -> Reader<'a> {
+        Reader {
+            bytes: self.bytes,
+            pos: self.pos,
+        }
+    }
+
+    // Runs a read on a Reader at the current position, advancing past what it read
+    fn with<T, E>(
+        &mut self,
+        f: impl FnOnce(&mut Reader<'a>) -> Result<T, &'static str>,
+    ) -> Result<T, Error<E>> {
+        let mut r = self.reader();
+        let x = f(&mut r).map_err(|_| Error::OutOfBounds)?;
+        self.pos = r.pos;
+        Ok(x)
+    }
+
+    fn read<E>(&mut self) -> Result<JsValue, Error<E>> {
+        // Op tags (mirrored from ser.rs)
+        Ok(match self.with(Reader::byte)? {
+            0 => JsValue::Null,
+            1 => JsValue::Bool(false),
+            2 => JsValue::Bool(true),
+            3 => JsValue::Number(f64::from_bits(self.with(Reader::u64)?)),
+            4 => JsValue::BigInt(self.with(Reader::u64)? as i128),
+            5 => JsValue::BigInt(self.with(Reader::u64)? as i64 as i128),
+            6 => {
+                let bytes = self.with(|r| {
+                    let len = r.u32()? as usize;
+                    r.take(len)
+                })?;
+                JsValue::String(String::from_utf8_lossy(bytes).into_owned())
+            }
+            7 => {
+                let len = self.with(Reader::u32)?;
+                JsValue::Array((0..len).map(|_| self.read()).collect::<Result<_, _>>()?)
+            }
+            8 => {
+                let len = self.with(Reader::u32)?;
+                let mut entries = Vec::new();
+                for _ in 0..len {
+                    let key = match self.read()? {
+                        JsValue::String(key) => key,
+                        JsValue::Number(x) => x.to_string(),
+                        JsValue::BigInt(x) => x.to_string(),
+                        JsValue::Bool(x) => x.to_string(),
+                        JsValue::Null => "null".to_string(),
+                        _ => return Err(Error::Unsupported("a map key that is not a primitive")),
+                    };
+                    entries.push((key, self.read()?));
+                }
+                JsValue::Object(entries)
+            }
+            // TYPED_ARRAY, with an offset into the data section, and VIEW, with a pointer into WebAssembly memory
+            tag @ (9 | 10) => {
+                let (array_type, start, len) =
+                    self.with(|r| Ok((r.byte()?, r.u32()?, r.u32()?)))?;
+                let array_type = ArrayType::from_index(array_type)
+                    .ok_or(Error::Unsupported("an array of unknown type"))?;
+                let (source, start) = match tag {
+                    9 => (self.bytes, 8 + start as usize),
+                    _ => (self.memory, start as usize),
+                };
+                let bytes = source
+                    .get(start..start + len as usize * array_type.size())
+                    .ok_or(Error::OutOfBounds)?;
+                JsValue::TypedArray(TypedArray::from_bytes(array_type, bytes))
+            }
+            _ => return Err(Error::Unsupported("a Structured value with an unknown op")),
+        })
+    }
+}
+
+fn u32_pair(value: f64) -> [u32; 2] {
+    let bits = value.to_bits();
+    [bits as u32, (bits >> 32) as u32]
+}
+
+impl ArrayType {
+    fn from_index(index: u8) -> Option<ArrayType> {
+        use ArrayType::*;
+        [U8, I8, U16, I16, U32, I32, F32, U64, I64, F64]
+            .get(index as usize)
+            .copied()
+    }
+}
+
+impl Transform {
+    fn from_index(index: u8) -> Option<Transform> {
+        use Transform::*;
+        [
+            U8Octet, I8Octet, U16Quartet, I16Quartet, U32Pair, I32Pair, F32Pair, AsU64, AsI64,
+            Identity, Void, Bool, String, Json, MsgPack, Structured, Iter, ViewRef,
+        ]
+        .get(index as usize)
+        .copied()
+    }
+}
+
+impl TypedArray {
+    // Decodes little-endian elements
+    fn from_bytes(array_type: ArrayType, bytes: &[u8]) -> TypedArray {
+        macro_rules! elements {
+            ($type:ty) => {
+                bytes
+                    .chunks_exact(size_of::<$type>())
+                    .map(|x| <$type>::from_le_bytes(x.try_into().unwrap()))
+                    .collect()
+            };
+        }
+        match array_type {
+            ArrayType::U8 | ArrayType::None => TypedArray::U8(bytes.to_vec()),
+            ArrayType::I8 => TypedArray::I8(elements!(i8)),
+            ArrayType::U16 => TypedArray::U16(elements!(u16)),
+            ArrayType::I16 => TypedArray::I16(elements!(i16)),
+            ArrayType::U32 => TypedArray::U32(elements!(u32)),
+            ArrayType::I32 => TypedArray::I32(elements!(i32)),
+            ArrayType::F32 => TypedArray::F32(elements!(f32)),
+            ArrayType::U64 => TypedArray::U64(elements!(u64)),
+            ArrayType::I64 => TypedArray::I64(elements!(i64)),
+            ArrayType::F64 => TypedArray::F64(elements!(f64)),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "wasmi"))]
+mod tests {
+    use super::*;
+
+    // A module exporting `add`, `none`, `fail`, `text` and `structured`, with the given extra exports, data and
+    // custom sections
+    fn module(abi_version: u32, extra: &str, sections: &[Vec<u8>]) -> Vec<u8> {
+        let pair = |ptr: u64, len: u64| (len << 32 | ptr) as i64;
+        let structured = structured();
+        let wat = format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (data (i32.const 16) "oops\00")
+                (data (i32.const 32) "hello")
+                (data (i32.const 64) "{}")
+                (func (export "to_js_abi_version") (result i32) i32.const {abi_version})
+                (func (export "add") (param f64 f64) (result f64) local.get 0 local.get 1 f64.add)
+                (func (export "none") (result f64) i64.const 0xfff8000000000000 f64.reinterpret_i64)
+                (func (export "fail") (result f64) i64.const 0xfff8000000000010 f64.reinterpret_i64)
+                (func (export "text") (result f64) i64.const {} f64.reinterpret_i64)
+                (func (export "structured") (result f64) i64.const {} f64.reinterpret_i64)
+                {extra})"#,
+            structured
+                .iter()
+                .map(|b| format!("\\{b:02x}"))
+                .collect::<String>(),
+            pair(32, 5),
+            pair(64, structured.len() as u64),
+        );
+        let mut wasm = wat::parse_str(wat).unwrap();
+        for section in sections {
+            wasm.push(0);
+            wasm.push(section.len() as u8);
+            wasm.extend_from_slice(section);
+        }
+        wasm
+    }
+
+    // {"a": 1.5, "xs": Uint32Array [7, 8], 2: [true, Uint8Array "hello" (a view)]}
+    fn structured() -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend(7u32.to_le_bytes());
+        data.extend(8u32.to_le_bytes());
+        let mut ops = vec![8];
+        ops.extend(3u32.to_le_bytes());
+        ops.extend([6, 1, 0, 0, 0, b'a', 3]);
+        ops.extend(1.5f64.to_le_bytes());
+        ops.extend([6, 2, 0, 0, 0, b'x', b's', 9, ArrayType::U32 as u8]);
+        ops.extend(0u32.to_le_bytes());
+        ops.extend(2u32.to_le_bytes());
+        ops.push(3);
+        ops.extend(2f64.to_le_bytes());
+        ops.extend([7, 2, 0, 0, 0, 2, 10, ArrayType::U8 as u8]);
+        ops.extend(32u32.to_le_bytes());
+        ops.extend(5u32.to_le_bytes());
+
+        let mut bytes = Vec::new();
+        bytes.extend((data.len() as u32).to_le_bytes());
+        bytes.extend((ops.len() as u32).to_le_bytes());
+        bytes.extend(data);
+        bytes.extend(ops);
+        bytes
+    }
+
+    fn manifest(entries: &[(u8, &str, [u8; 8])]) -> Vec<u8> {
+        let mut section = vec![14];
+        section.extend(b"to_js_manifest");
+        for (kind, name, info) in entries {
+            section.push(*kind);
+            section.extend((name.len() as u32).to_le_bytes());
+            section.extend(name.as_bytes());
+            section.push(1);
+            section.extend(info);
+        }
+        section
+    }
+
+    fn host(wasm: &[u8]) -> Result<Host<WasmiInstance<()>>, Error<::wasmi::Error>> {
+        let engine = ::wasmi::Engine::default();
+        let module = ::wasmi::Module::new(&engine, wasm).unwrap();
+        let mut store = ::wasmi::Store::new(&engine, ());
+        let instance = ::wasmi::Linker::new(&engine)
+            .instantiate(&mut store, &module)
+            .unwrap()
+            .start(&mut store)
+            .unwrap();
+        Host::new(WasmiInstance::new(store, instance), wasm)
+    }
+
+    const NUMBER: [u8; 8] = [0, 0, 0, 10, 9, 0, 0, 0];
+    const OPTION: [u8; 8] = [0, 1, 0, 10, 9, 0, 0, 0];
+    const RESULT: [u8; 8] = [1, 0, 0, 10, 9, 0, 0, 0];
+    const STRING: [u8; 8] = [0, 0, 1, 0, 12, 0, 0, 0];
+    const STRUCTURED: [u8; 8] = [0, 0, 1, 0, 15, 0, 0, 0];
+
+    #[test]
+    fn decodes_values_described_by_the_manifest() {
+        let manifest = manifest(&[
+            (crate::manifest::EXPORT, "add", NUMBER),
+            (crate::manifest::EXPORT, "none", OPTION),
+            (crate::manifest::EXPORT, "fail", RESULT),
+            (crate::manifest::EXPORT, "text", STRING),
+            (crate::manifest::EXPORT, "structured", STRUCTURED),
+            (crate::manifest::IMPORT, "log", NUMBER),
+        ]);
+        let mut rs = host(&module(ABI_VERSION, "", &[manifest])).unwrap();
+        assert_eq!(
+            rs.functions().collect::<Vec<_>>(),
+            ["add", "fail", "none", "structured", "text"]
+        );
+        assert_eq!(rs.call("add", &[2.0, 2.0]).unwrap(), JsValue::Number(4.0));
+        assert_eq!(rs.call("none", &[]).unwrap(), JsValue::Null);
+        assert!(matches!(rs.call("fail", &[]), Err(Error::Err(e)) if e == "oops"));
+        assert_eq!(
+            rs.call("text", &[]).unwrap(),
+            JsValue::String("hello".into())
+        );
+        assert_eq!(
+            rs.call("structured", &[]).unwrap(),
+            JsValue::Object(vec![
+                ("a".into(), JsValue::Number(1.5)),
+                (
+                    "xs".into(),
+                    JsValue::TypedArray(TypedArray::U32(vec![7, 8]))
+                ),
+                (
+                    "2".into(),
+                    JsValue::Array(vec![
+                        JsValue::Bool(true),
+                        JsValue::TypedArray(TypedArray::U8(b"hello".to_vec())),
+                    ])
+                ),
+            ])
+        );
+        assert!(matches!(rs.call("log", &[]), Err(Error::NotFound(_))));
+    }
+
+    #[test]
+    fn falls_back_to_info_exports() {
+        let info = |name: &str, info: [u8; 8]| {
+            let info = i64::from_le_bytes(info);
+            format!(
+                r#"(func (export "{name}_info_") (result f64) i64.const {info} f64.reinterpret_i64)"#
+            )
+        };
+        let extra = info("add", NUMBER) + &info("text", STRING);
+        let mut rs = host(&module(ABI_VERSION, &extra, &[])).unwrap();
+        assert_eq!(rs.functions().collect::<Vec<_>>(), ["add", "text"]);
+        assert_eq!(rs.call("add", &[1.0, 2.0]).unwrap(), JsValue::Number(3.0));
+        assert_eq!(
+            rs.call("text", &[]).unwrap(),
+            JsValue::String("hello".into())
+        );
+    }
+
+    #[test]
+    fn rejects_modules_without_type_info_or_with_another_abi() {
+        let stripped = r#"(func (export "to_js_no_info_exports"))"#;
+        assert!(matches!(
+            host(&module(ABI_VERSION, stripped, &[])),
+            Err(Error::InvalidModule(_))
+        ));
+        assert!(matches!(
+            host(&module(ABI_VERSION + 1, "", &[])),
+            Err(Error::AbiVersion(Some(v))) if v == ABI_VERSION + 1
+        ));
+        let mut truncated = manifest(&[(crate::manifest::EXPORT, "add", NUMBER)]);
+        truncated.truncate(truncated.len() - 4);
+        assert!(matches!(
+            host(&module(ABI_VERSION, "", &[truncated])),
+            Err(Error::InvalidModule(_))
+        ));
+    }
+}
@@ -0,0 +1,75 @@
+// Implementation of `Instance` for the wasmi interpreter
+
+use super::Instance;
+use wasmi::core::ValType;
+use wasmi::{AsContext, Store, Val};
+
+/// A wasmi instance and the store that owns it, which must export its memory as `memory`.
+pub struct WasmiInstance<T> {
+    store: Store<T>,
+    instance: wasmi::Instance,
+}
+
+impl<T> WasmiInstance<T> {
+    pub fn new(store: Store<T>, instance: wasmi::Instance) -> Self {
+        WasmiInstance { store, instance }
+    }
+
+    pub fn store(&mut self) -> &mut Store<T> {
+        &mut self.store
+    }
+}
+
+impl<T> Instance for WasmiInstance<T> {
+    type Error = wasmi::Error;
+
+    fn functions(&self) -> Vec<String> {
+        self.instance
+            .exports(&self.store)
+            .filter(|export| export.ty(&self.store).func().is_some())
+            .map(|export| export.name().to_string())
+            .collect()
+    }
+
+    fn call(&mut self, name: &str, args: &[f64]) -> Result<f64, wasmi::Error> {
+        let func = self
+            .instance
+            .get_func(&self.store, name)
+            .ok_or_else(|| wasmi::Error::new(format!("missing export: {name}")))?;
+        let ty = func.ty(&self.store);
+        if args.len() != ty.params().len() {
+            return Err(wasmi::Error::new(format!(
+                "{name}: expected {} arguments, got {}",
+                ty.params().len(),
+                args.len()
+            )));
+        }
+        let params: Vec<Val> = ty
+            .params()
+            .iter()
+            .zip(args)
+            .map(|(ty, &x)| match ty {
+                ValType::I32 => Val::I32(x as i64 as i32),
+                ValType::I64 => Val::I64(x as i64),
+                ValType::F32 => Val::F32((x as f32).into()),
+                _ => Val::F64(x.into()),
+            })
+            .collect();
+        let mut results: Vec<Val> = ty.results().iter().map(|&ty| Val::default(ty)).collect();
+        func.call(&mut self.store, &params, &mut results)?;
+        Ok(match results.first() {
+            Some(Val::I32(x)) => *x as f64,
+            Some(Val::I64(x)) => *x as f64,
+            Some(Val::F32(x)) => f32::from(*x) as f64,
+            Some(Val::F64(x)) => f64::from(*x),
+            _ => 0.0,
+        })
+    }
+
+    fn memory(&self) -> &[u8] {
+        match self.instance.get_memory(self.store.as_context(), "memory") {
+            Some(memory) => memory.data(&self.store),
+            None => &[],
+        }
+    }
+}
@@ -2,7 +2,31 @@
 // If the optional second argument is true, typed arrays (including ones that
 // were stashed or returned as packed arrays) will always be copied out of the
 // WebAssembly heap before being returned.
-export function wrap(instance, alwaysCopyData) {
+// The optional third argument supplies implementations of the functions imported via #[js_import]
+// (`imports`), and the object (`handlers`) through which the module's `to_js` imports were forwarded
+// to them at instantiation time, into which wrap installs the argument-decoding import handlers.
+// It can also specify whether to generate code for each export (`compile`), which is the default
+// unless code generation is disallowed, or supply wrappers generated ahead of time (`precompiled`),
+// as is done by the modules generated by the `to_js` binary.
+// The type info of each function is read from the manifest that the module exports (or, for modules without one,
+// from companion exports). The result of `WebAssembly.instantiate` (or `instantiateStreaming`) can also be passed
+// as the first argument.
+function wrap(instance, alwaysCopyData, { imports = {}, handlers = {}, compile, precompiled } = {}) {
+	if (instance.instance !== undefined) {
+		instance = instance.instance;
+	}
+
+	// The version of the encoding of values and type info that this code decodes (see ABI_VERSION in typeinfo.rs),
+	// which must match the module's, since a mismatch would otherwise silently produce incorrect values
+	const ABI_VERSION = 2;
+	const abiVersion = instance.exports.to_js_abi_version?.();
+	if (abiVersion !== ABI_VERSION) {
+		throw new Error(
+			`to_js ABI version mismatch: this glue code decodes version ${ABI_VERSION}, but the module ` +
+			(abiVersion === undefined ? `does not export one (it may predate versioning)` : `uses version ${abiVersion}`)
+		);
+	}
+
 	// In enum variant order (enum: ArrayType)
 	const arrayTypes = [
 		Uint8Array,
@@ -54,8 +78,320 @@ export function wrap(instance, alwaysCopyData) {
 		Boolean,
 		(x) => textDecoder.decode(x),
 		(x) => JSON.parse(textDecoder.decode(x)),
+		decodeMsgPack,
+		decodeStructured,
+		iterate,
+		(x) => new ViewRef(x),
 	];
 
+	// Add an entry of a decoded map to an object. Assigning would call the `__proto__` setter for that key, changing
+	// the object's prototype, rather than adding a property (which is what JSON.parse does).
+	function defineEntry(obj, key, value) {
+		Object.defineProperty(obj, key, { value, enumerable: true, writable: true, configurable: true });
+	}
+
+	// Decode a MessagePack-encoded value. Integers that need 64 bits are decoded as BigInts,
+	// and byte strings (bin) are decoded as Uint8Arrays, which are views into WebAssembly
+	// memory unless alwaysCopyData is true.
+	function decodeMsgPack(bytes) {
+		const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+		let pos = 0;
+
+		function num(getter, size) {
+			const x = view[getter](pos);
+			pos += size;
+			return x;
+		}
+
+		const u8 = () => num("getUint8", 1);
+		const u16 = () => num("getUint16", 2);
+		const u32 = () => num("getUint32", 4);
+
+		function bin(len) {
+			const data = bytes.subarray(pos, pos += len);
+			return alwaysCopyData ? data.slice() : data;
+		}
+
+		function str(len) {
+			return textDecoder.decode(bytes.subarray(pos, pos += len));
+		}
+
+		function array(len) {
+			const arr = new Array(len);
+			for (let i = 0; i < len; i++) arr[i] = read();
+			return arr;
+		}
+
+		function map(len) {
+			const obj = {};
+			for (let i = 0; i < len; i++) {
+				const key = read();
+				defineEntry(obj, key, read());
+			}
+			return obj;
+		}
+
+		function ext(len) {
+			const type = num("getInt8", 1);
+			return { type, data: bin(len) };
+		}
+
+		function read() {
+			const b = u8();
+			if (b < 0x80) return b;
+			if (b < 0x90) return map(b & 0x0f);
+			if (b < 0xa0) return array(b & 0x0f);
+			if (b < 0xc0) return str(b & 0x1f);
+			if (b >= 0xe0) return b - 0x100;
+			switch (b) {
+				case 0xc0: return null;
+				case 0xc2: return false;
+				case 0xc3: return true;
+				case 0xc4: return bin(u8());
+				case 0xc5: return bin(u16());
+				case 0xc6: return bin(u32());
+				case 0xc7: return ext(u8());
+				case 0xc8: return ext(u16());
+				case 0xc9: return ext(u32());
+				case 0xca: return num("getFloat32", 4);
+				case 0xcb: return num("getFloat64", 8);
+				case 0xcc: return u8();
+				case 0xcd: return u16();
+				case 0xce: return u32();
+				case 0xcf: return num("getBigUint64", 8);
+				case 0xd0: return num("getInt8", 1);
+				case 0xd1: return num("getInt16", 2);
+				case 0xd2: return num("getInt32", 4);
+				case 0xd3: return num("getBigInt64", 8);
+				case 0xd4: return ext(1);
+				case 0xd5: return ext(2);
+				case 0xd6: return ext(4);
+				case 0xd7: return ext(8);
+				case 0xd8: return ext(16);
+				case 0xd9: return str(u8());
+				case 0xda: return str(u16());
+				case 0xdb: return str(u32());
+				case 0xdc: return array(u16());
+				case 0xdd: return array(u32());
+				case 0xde: return map(u16());
+				case 0xdf: return map(u32());
+			}
+			throw new Error(`MessagePack: unsupported type byte 0x${b.toString(16)}`);
+		}
+
+		return read();
+	}
+
+	// Decode a value encoded by the Rust-side serde serializer for `Structured` values: an 8-byte header
+	// holding the lengths of a data section and an op stream, followed by both. Numeric sequences are stored in
+	// the data section, or referred to by pointer if they were serialized with `as_view`, and are decoded as typed
+	// array views into WebAssembly memory (or copies, if alwaysCopyData is true).
+	function decodeStructured(bytes) {
+		const view = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength);
+		const dataOffset = bytes.byteOffset + 8;
+		let pos = 8 + view.getUint32(0, true);
+
+		function u32() {
+			const x = view.getUint32(pos, true);
+			pos += 4;
+			return x;
+		}
+
+		// In op tag order (mirrored from the Rust side)
+		const ops = [
+			() => null,
+			() => false,
+			() => true,
+			() => (pos += 8, view.getFloat64(pos - 8, true)),
+			() => (pos += 8, view.getBigUint64(pos - 8, true)),
+			() => (pos += 8, view.getBigInt64(pos - 8, true)),
+			() => {
+				const len = u32();
+				return textDecoder.decode(bytes.subarray(pos, pos += len));
+			},
+			() => {
+				const arr = new Array(u32());
+				for (let i = 0; i < arr.length; i++) arr[i] = read();
+				return arr;
+			},
+			() => {
+				const obj = {};
+				for (let i = u32(); i > 0; i--) {
+					const key = read();
+					defineEntry(obj, key, read());
+				}
+				return obj;
+			},
+			() => {
+				const T = arrayTypes[view.getUint8(pos++)];
+				const offset = u32();
+				const arr = new T(bytes.buffer, dataOffset + offset, u32());
+				return alwaysCopyData ? arr.slice() : arr;
+			},
+			() => {
+				const T = arrayTypes[view.getUint8(pos++)];
+				const ptr = u32();
+				const arr = new T(bytes.buffer, ptr, u32());
+				return alwaysCopyData ? arr.slice() : arr;
+			},
+		];
+
+		function read() {
+			return ops[view.getUint8(pos++)]();
+		}
+
+		return read();
+	}
+
+	// Drop the Rust-side iterator if a JsIter is garbage collected before being exhausted
+	const iterRegistry = new FinalizationRegistry((handle) => instanceExports.to_js_iter_drop(handle));
+
+	// Return an iterable over the chunks of a JsIter, each of which is pulled from Rust by a call to
+	// `to_js_iter_next`. Since pulling a chunk frees the previous one, chunks are copied out of WebAssembly memory,
+	// so that they can be collected (eg. with `Array.from`). Iterating over `views()` instead yields typed array
+	// views into WebAssembly memory (unless alwaysCopyData is true), each of which is only valid until the next
+	// chunk is pulled.
+	function iterate(handle, T) {
+		let done = false;
+
+		function pull(copy) {
+			if (done) return { done, value: undefined };
+			const [ptr, len] = u32Pair(instanceExports.to_js_iter_next(handle));
+			if (len === 0) return iter.return();
+			const chunk = new T(instanceExports.memory.buffer, ptr, len);
+			return { done, value: copy ? chunk.slice() : chunk };
+		}
+
+		const iter = {
+			[Symbol.iterator]() {
+				return this;
+			},
+			next() {
+				return pull(true);
+			},
+			return() {
+				if (!done) {
+					done = true;
+					iterRegistry.unregister(iter);
+					instanceExports.to_js_iter_drop(handle);
+				}
+				return { done, value: undefined };
+			},
+			views() {
+				return {
+					[Symbol.iterator]() {
+						return this;
+					},
+					next: () => pull(alwaysCopyData),
+					return: () => iter.return(),
+				};
+			},
+		};
+		iterRegistry.register(iter, handle, iter);
+		return iter;
+	}
+
+	// A typed array view into WebAssembly memory that survives memory growth, which detaches all existing views
+	// (making them appear empty). Rather than holding a fixed view, this records the array's location and
+	// re-derives the view from it whenever the memory's buffer has changed since the view was last accessed.
+	class ViewRef {
+		constructor(view) {
+			this.view = view;
+			this.byteOffset = view.byteOffset;
+			this.length = view.length;
+		}
+
+		get array() {
+			const buffer = instanceExports.memory.buffer;
+			if (this.view.buffer !== buffer) {
+				this.view = new this.view.constructor(buffer, this.byteOffset, this.length);
+			}
+			return this.view;
+		}
+	}
+
+	// An array allocated in WebAssembly memory by allocArray, for JavaScript to fill and pass to an exported function
+	// that takes ownership of it (as an Owned value). Until then, it can be freed with `free`. Since allocating may
+	// grow memory, the array is re-derived from its location like a ViewRef.
+	class OwnedArray extends ViewRef {
+		moved = false;
+
+		get array() {
+			if (this.moved) throw new Error("OwnedArray: the array has been moved to Rust");
+			return super.array;
+		}
+
+		move() {
+			if (this.moved) throw new Error("OwnedArray: the array has already been moved to Rust");
+			this.moved = true;
+			typedArrays[4].set([this.byteOffset, this.length]);
+			return f64Array[0];
+		}
+
+		free() {
+			if (!this.moved) {
+				this.moved = true;
+				const T = this.view.constructor;
+				instanceExports.to_js_free_bytes(this.byteOffset, this.length * T.BYTES_PER_ELEMENT, T.BYTES_PER_ELEMENT);
+			}
+		}
+	}
+
+	// Allocate an array of the given typed array type and length in WebAssembly memory
+	function allocArray(T, length) {
+		if (!arrayTypes.includes(T)) throw new Error(`allocArray: expected a typed array type, got ${T?.name ?? T}`);
+		const size = length * T.BYTES_PER_ELEMENT;
+		// Lengths that are not valid, or whose size does not fit in a u32, are not passed to Rust (where they would wrap
+		// around), and sizes that are too large to allocate return a null pointer
+		const ptr = Number.isInteger(length) && length >= 0 && size <= 0xffffffff ? instanceExports.to_js_alloc_bytes(size, T.BYTES_PER_ELEMENT) : 0;
+		if (ptr === 0) throw new RangeError(`allocArray: cannot allocate ${length} elements of ${T.name}`);
+		// Allocating may have grown memory, detaching previously returned views
+		checkMemory();
+		return new OwnedArray(new T(instanceExports.memory.buffer, ptr, length));
+	}
+
+	// In debug builds, keep weak references to the typed array views returned from exported functions so that
+	// we can warn if any are still in use when memory growth detaches them.
+	const debug = instanceExports.to_js_debug_assertions?.() === 1;
+	let buffer = instanceExports.memory.buffer;
+	let views = [];
+
+	function trackView(view) {
+		views.push(new WeakRef(view));
+		// Periodically forget views that have been garbage collected
+		if (views.length % 1024 === 0) views = views.filter((d) => d.deref() !== undefined);
+	}
+
+	function checkMemory() {
+		if (!debug) return;
+		const oldBuffer = buffer;
+		buffer = instanceExports.memory.buffer;
+		if (buffer === oldBuffer) return;
+		const detached = views.filter((d) => d.deref()?.buffer === oldBuffer);
+		views = [];
+		if (detached.length > 0) {
+			// Views that the caller has already dropped stay alive until they are garbage collected, so only warn about
+			// those that are still alive once the current task has finished and the garbage collector has had a chance
+			// to run (which it is asked to do if it is exposed, eg. with node --expose-gc).
+			const timeout = setTimeout(() => warnDetached(detached), DETACHED_VIEW_CHECK_DELAY);
+			timeout.unref?.();
+		}
+	}
+
+	// The delay in milliseconds before checking whether views detached by memory growth are still alive
+	const DETACHED_VIEW_CHECK_DELAY = 1000;
+
+	function warnDetached(detached) {
+		globalThis.gc?.();
+		const count = detached.filter((d) => d.deref() !== undefined).length;
+		if (count > 0) {
+			console.warn(
+				`WebAssembly memory grew, detaching ${count} typed array${count === 1 ? '' : 's'} previously returned from Rust. ` +
+				`Return a ViewRef to re-derive the view after memory growth, or pass alwaysCopyData to wrap() to return copies.`
+			);
+		}
+	}
+
 	function cString(ptr) {
 		const bytes = new Uint8Array(instanceExports.memory.buffer, ptr);
 		const end = bytes.findIndex((d) => d === 0);
@@ -69,7 +405,7 @@ export function wrap(instance, alwaysCopyData) {
 	// Implement decoding for both niche strategies
 	
 	function tryResultHighBitsNaN(pair) {
-		if (pair[0] !== 1 && pair[1] === 0xfff80000) {
+		if (pair[0] !== 0 && pair[1] === 0xfff80000) {
 			throwError(pair[0]);
 		}
 	}
@@ -96,51 +432,428 @@ export function wrap(instance, alwaysCopyData) {
 		return isArray ? tryResultLowBitsOne : tryResultHighBitsNaN;
 	}
 
-	return Object.fromEntries(
-		Object.keys(instanceExports)
-			.filter((d) => d.endsWith("_info_"))
-			.map((nameWithSuffix) => {
-				const name = nameWithSuffix.slice(0, -6);
-				const typeInfo = u8Octet(instanceExports[`${name}_info_`]());
-				const [isResult, isOption, isArray, arrayType, transformIndex] = typeInfo;
-				const numArgs = instanceExports[name].length;
-				const args = Array.from({ length: numArgs }, (_, i) => `x${i + 1}`);
-				const argsAsString = args.join(", ");
-				const needsPair = isResult || isOption || isArray;
-				const isPackedArray = transformIndex < 7;
-				const isIdentityTransform = transformIndex === 9;
-				const slice = alwaysCopyData && (isPackedArray || (isArray && isIdentityTransform));
-				// Compile a specialized function for each export using basic dead-code elimination to elide
-				// unnecessary transformations (eg. only include Option-processing code if the return value is an Option).
-				const fn = new Function(`exports`, `tryResult`, `tryOption`, `transform`, `u32Pair`, `
-					return function ${name}(${argsAsString}) {
-						if (arguments.length !== ${args.length}) {
-							throw new Error(\`${name}: expected ${args.length} argument${args.length === 1 ? '' : 's'}, got \${arguments.length}\`);
-						}
-						let value = exports.${name}(${argsAsString});
-						${needsPair ? `const pair = u32Pair(value);` : ``}
-						${isResult ? `tryResult(pair);` : ``}
-						${isOption ? `if (tryOption(pair)) return null;` : ``}
-						${isArray ? `value = new ${arrayTypes[arrayType].name}(exports.memory.buffer, pair[0], pair[1])` : ``}
-						const ret = transform(value);
-						return ${slice ? `ret.slice()` : `ret`}
-					}`);
-				return [
-					name,
-					fn(
-						instanceExports,
-						tryResult(isArray),
-						tryOption(isArray),
-						outputTransforms[transformIndex],
-						u32Pair
-					)
-				];
-			})
-	);
+	// Whether a value with the given type info should be copied out of WebAssembly memory if alwaysCopyData is true
+	function shouldCopy(isArray, transformIndex) {
+		const isPackedArray = transformIndex < 7;
+		const isIdentityTransform = transformIndex === 9;
+		return alwaysCopyData && (isPackedArray || (isArray && isIdentityTransform));
+	}
+
+	// Whether a value with the given type info is a view into WebAssembly memory that should be tracked in debug builds
+	function shouldTrack(isArray, transformIndex) {
+		return debug && !alwaysCopyData && isArray && transformIndex === 9;
+	}
+
+	// Return a function that decodes values with the given type info.
+	// This is a closure-based equivalent of the decoding code compiled for each export below.
+	function decoder(typeInfo) {
+		const [isResult, isOption, isArray, arrayType, transformIndex, , isPinned] = typeInfo;
+		const needsPair = isResult || isOption || isArray;
+		const tryRes = tryResult(isArray && !isPinned);
+		const tryOpt = tryOption(isArray && !isPinned);
+		const transform = outputTransforms[transformIndex];
+		const T = arrayTypes[arrayType];
+		const slice = shouldCopy(isArray, transformIndex);
+		const track = shouldTrack(isArray, transformIndex);
+		return (value) => {
+			let pair = needsPair && u32Pair(value);
+			if (isResult) tryRes(pair);
+			if (isOption && tryOpt(pair)) return null;
+			const handle = value;
+			if (isPinned) value = instanceExports.to_js_pinned_value(handle);
+			if (isPinned && isArray) pair = u32Pair(value);
+			if (isArray) value = new T(instanceExports.memory.buffer, pair[0], pair[1]);
+			const ret = transform(value, T);
+			if (track) trackView(ret);
+			return isPinned ? pinned(slice ? ret.slice() : ret, handle) : slice ? ret.slice() : ret;
+		};
+	}
+
+	// Return a value that is kept alive (as a Pinned value) until `release` is called
+	function pinned(value, handle) {
+		let released = false;
+		return {
+			value,
+			release() {
+				if (!released) {
+					released = true;
+					instanceExports.to_js_release(handle);
+				}
+			},
+		};
+	}
+
+	// Read the type info of each function from the module's manifest. Each entry is exported as the address of a
+	// static (`to_js_manifest_export_<name>` or `to_js_manifest_import_<name>`) of the form
+	// [kind: u8, name length: u32, name, info count: u8, info: 8 bytes each] (see manifest.rs).
+	// Exports (kind 0) have the info for their return value, and imports (kind 1) have the info for each argument.
+	// Returns null if the module has no manifest.
+	function readManifest() {
+		const entries = Object.keys(instanceExports).filter((name) => name.startsWith("to_js_manifest_"));
+		if (entries.length === 0) return null;
+		const manifest = { exports: new Map(), imports: new Map() };
+		const memory = instanceExports.memory.buffer;
+		const view = new DataView(memory);
+		for (const entry of entries) {
+			const offset = instanceExports[entry].value;
+			const kind = view.getUint8(offset);
+			const nameLength = view.getUint32(offset + 1, true);
+			const name = textDecoder.decode(new Uint8Array(memory, offset + 5, nameLength));
+			const count = view.getUint8(offset + 5 + nameLength);
+			const start = offset + 6 + nameLength;
+			// The infos are copied out of memory, which may grow
+			const infos = Array.from({ length: count }, (_, i) => new Uint8Array(memory.slice(start + 8 * i, start + 8 * i + 8)));
+			if (kind === 0) manifest.exports.set(name, infos[0]);
+			else manifest.imports.set(name, infos);
+		}
+		return manifest;
+	}
+
+	// Without a manifest, the type info of each function is read from its companion exports
+	function readInfoExports() {
+		if (instanceExports.to_js_no_info_exports) {
+			throw new Error(
+				"This module was built without the `_info_` exports (as builds without debug assertions are), and does " +
+				"not export a manifest, so its type info cannot be read: rebuild it with this version of to_js, or " +
+				"enable the `info-exports` feature"
+			);
+		}
+		const manifest = { exports: new Map(), imports: new Map() };
+		for (const exportName of Object.keys(instanceExports)) {
+			if (exportName.endsWith("_info_") && !precompiled) {
+				manifest.exports.set(exportName.slice(0, -6), u8Octet(instanceExports[exportName]()).slice());
+			} else if (exportName.startsWith("to_js_import_")) {
+				const [ptr, len] = u32Pair(instanceExports[exportName]());
+				const infos = Array.from(new Float64Array(instanceExports.memory.buffer, ptr, len), (x) => u8Octet(x).slice());
+				manifest.imports.set(exportName.slice("to_js_import_".length), infos);
+			}
+		}
+		return manifest;
+	}
+
+	// Precompiled wrappers include the type info of the module's imports if it has a manifest
+	const manifest = precompiled?.imports
+		? { exports: new Map(), imports: new Map(Object.entries(precompiled.imports)) }
+		: readManifest() ?? readInfoExports();
+
+	// Install handlers for the functions imported via #[js_import]. Arguments that are typed arrays are views into
+	// WebAssembly memory that are only valid for the duration of the call. Return values are passed back to Rust as numbers.
+	// Exceptions thrown by the functions (or by exported functions that they call, including traps) unwind the Rust
+	// frames that called them without running destructors, so Rust is told to restore its call depth (see JsCall in
+	// keepalive.rs) before they propagate. The same goes for the JsFn trampoline below.
+	for (const [name, infos] of manifest.imports) {
+		const fn = imports[name];
+		if (typeof fn !== "function") throw new Error(`Missing import: ${name}`);
+		const decoders = infos.map(decoder);
+		handlers[name] = (...args) => {
+			try {
+				return Number(fn(...args.map((x, i) => decoders[i](x))) ?? 0);
+			} catch (e) {
+				instanceExports.to_js_unwind();
+				throw e;
+			}
+		};
+	}
+
+	// JavaScript functions passed as arguments to exported functions (as JsFn) are registered in this table for the
+	// duration of the call, and Rust receives their index. Nested calls register their functions above those of
+	// the outer calls, so the table can be truncated to its previous length once each call returns. Only the
+	// wrappers of functions whose type info has the converts_args flag set (see ArgKind in typeinfo.rs) do this.
+	const callbacks = [];
+
+	// Arrays allocated with allocArray are passed to Rust (as Owned values) as (ptr, len) pairs
+	function arg(x) {
+		if (typeof x === "function") return callbacks.push(x) - 1;
+		if (x instanceof OwnedArray) return x.move();
+		return x;
+	}
+
+	// Decoders for the arguments of JsFn calls, by f64-encoded type info
+	const argDecoders = new Map();
+
+	// Trampoline through which Rust calls JsFn functions, whose arguments are passed as (value, type info) pairs
+	handlers.to_js_invoke = (index, ptr, len) => {
+		const pairs = new Float64Array(instanceExports.memory.buffer, ptr, 2 * len);
+		const args = new Array(len);
+		for (let i = 0; i < len; i++) {
+			const info = pairs[2 * i + 1];
+			let decode = argDecoders.get(info);
+			if (decode === undefined) {
+				decode = decoder(u8Octet(info));
+				argDecoders.set(info, decode);
+			}
+			args[i] = decode(pairs[2 * i]);
+		}
+		try {
+			return Number(callbacks[index](...args) ?? 0);
+		} catch (e) {
+			instanceExports.to_js_unwind();
+			throw e;
+		}
+	};
+
+	// Log levels (enum: console::Level)
+	const logLevels = ["off", "error", "warn", "info", "debug", "trace"];
+	const consoleMethods = [null, "error", "warn", "log", "debug", "debug"];
+
+	// Default implementation of the import used by console_log! and friends, and by the `log` crate backend
+	handlers.to_js_console = (level, ptr, len) => {
+		console[consoleMethods[level]](textDecoder.decode(new Uint8Array(instanceExports.memory.buffer, ptr, len)));
+	};
+
+	// Default implementation of the import used by the `tracing` feature to record spans and events with the
+	// Performance API. Entering a span (kind 0) notes the time, exiting it (kind 1) measures the time since,
+	// and events (kind 2) are recorded as marks. Span ids are u64s, received as BigInts.
+	const spanStarts = new Map();
+	handlers.to_js_performance = (kind, id, ptr, len) => {
+		const name = textDecoder.decode(new Uint8Array(instanceExports.memory.buffer, ptr, len));
+		if (kind === 0) {
+			spanStarts.set(id, performance.now());
+		} else if (kind === 1) {
+			performance.measure(name, { start: spanStarts.get(id) });
+			spanStarts.delete(id);
+		} else {
+			performance.mark(name);
+		}
+	};
+
+	// Call `fn`, keeping values returned from Rust in a KeepAlive alive until it returns, rather than only until the
+	// next call into Rust. Scopes can be nested, in which case values are kept alive until the outermost one ends.
+	function scope(fn) {
+		instanceExports.to_js_scope_enter();
+		try {
+			return fn();
+		} finally {
+			instanceExports.to_js_scope_exit();
+		}
+	}
+
+	// Return statistics about memory held on behalf of JavaScript (see stats.rs), for diagnosing leaks
+	function stats() {
+		const [ptr, len] = u32Pair(instanceExports.to_js_stats());
+		return JSON.parse(textDecoder.decode(new Uint8Array(instanceExports.memory.buffer, ptr, len)));
+	}
+
+	// Set the maximum level of messages logged to the console from Rust
+	function setLogLevel(level) {
+		const index = logLevels.indexOf(level);
+		if (index === -1) throw new Error(`Unknown log level: ${level}`);
+		instanceExports.to_js_set_log_level(index);
+	}
+
+	// Promises for pending JsPromise tasks, by task handle
+	const tasks = new Map();
+	let pollScheduled = false;
+
+	function promise(handle, decode) {
+		return new Promise((resolve, reject) => {
+			tasks.set(handle, { resolve, reject, decode });
+			schedulePoll();
+		});
+	}
+
+	function schedulePoll() {
+		if (!pollScheduled) {
+			pollScheduled = true;
+			queueMicrotask(poll);
+		}
+	}
+
+	// Any call may wake a pending task (eg. by providing data it is waiting on), so poll after each one.
+	// Any call may also grow memory, so check whether it did.
+	function afterCall() {
+		checkMemory();
+		if (tasks.size > 0) schedulePoll();
+	}
+
+	// Poll woken tasks, settling the promises of those that have completed.
+	// This is also exposed so that tasks woken by other means can be driven explicitly.
+	function poll() {
+		pollScheduled = false;
+		const [ptr, len] = u32Pair(instanceExports.to_js_poll());
+		const completed = new Uint32Array(instanceExports.memory.buffer, ptr, len).slice();
+		for (const handle of completed) {
+			const task = tasks.get(handle);
+			// Skip tasks whose promises were not created by this wrapper (eg. if the instance was wrapped twice)
+			if (task === undefined) continue;
+			const { resolve, reject, decode } = task;
+			tasks.delete(handle);
+			try {
+				resolve(decode(instanceExports.to_js_task_result(handle)));
+			} catch (e) {
+				reject(e);
+			}
+		}
+	}
+
+	// The arguments of the function that returns a compiled or precompiled wrapper
+	function wrapperArgs(typeInfo) {
+		const [, , isArray, arrayType, transformIndex, , isPinned] = typeInfo;
+		return [
+			instanceExports,
+			tryResult(isArray && !isPinned),
+			tryOption(isArray && !isPinned),
+			outputTransforms[transformIndex],
+			u32Pair,
+			arrayTypes[arrayType],
+			promise,
+			afterCall,
+			arg,
+			callbacks,
+			trackView,
+			pinned,
+			shouldCopy(isArray, transformIndex),
+			shouldTrack(isArray, transformIndex),
+		];
+	}
+
+	// Use a wrapper whose source was generated ahead of time (see src/bin/to_js.rs)
+	function precompiledWrapper(name) {
+		const [typeInfo, fn] = precompiled.exports[name];
+		return fn(...wrapperArgs(typeInfo));
+	}
+
+	// begin: runtime wrappers (omitted from the glue code generated by the to_js binary, which supplies `precompiled`)
+
+	// The parameters of the function whose source is returned by wrapperSource (see wrapperArgs for the arguments)
+	const wrapperParams = [`exports`, `tryResult`, `tryOption`, `transform`, `u32Pair`, `arrayType`, `promise`, `afterCall`, `arg`, `callbacks`, `trackView`, `pinned`, `slice`, `track`];
+
+	// Words that cannot be used as the name of a function in strict mode code
+	const reservedWords = new Set([
+		"arguments", "await", "break", "case", "catch", "class", "const", "continue", "debugger", "default", "delete",
+		"do", "else", "enum", "eval", "export", "extends", "false", "finally", "for", "function", "if", "implements",
+		"import", "in", "instanceof", "interface", "let", "new", "null", "package", "private", "protected", "public",
+		"return", "static", "super", "switch", "this", "throw", "true", "try", "typeof", "var", "void", "while", "with",
+		"yield",
+	]);
+
+	// Whether a name can be used as the name of a function (see also is_identifier in src/bin/to_js.rs)
+	function isIdentifier(name) {
+		return /^[\p{ID_Start}_$][\p{ID_Continue}$\u200c\u200d]*$/u.test(name) && !reservedWords.has(name);
+	}
+
+	// The generated wrapper is named after the export, so exports whose names are not identifiers use closures
+	function compiledWrapper(name, typeInfo) {
+		if (!isIdentifier(name)) return closureWrapper(name, typeInfo);
+		const fn = new Function(...wrapperParams, wrapperSource(name, typeInfo, instanceExports[name].length, arrayTypes));
+		return fn(...wrapperArgs(typeInfo));
+	}
+
+	// The equivalent of compiledWrapper without code generation, which decodes values using a closure-based decoder.
+	function closureWrapper(name, typeInfo) {
+		const isPromise = typeInfo[5];
+		const convertsArgs = typeInfo[7];
+		const decode = decoder(typeInfo);
+		const fn = instanceExports[name];
+		const numArgs = fn.length;
+		// Define the function as a method in order to give it the export's name
+		return {
+			[name](...args) {
+				if (args.length !== numArgs) {
+					throw new Error(`${name}: expected ${numArgs} argument${numArgs === 1 ? '' : 's'}, got ${args.length}`);
+				}
+				if (!convertsArgs) {
+					const value = fn(...args);
+					afterCall();
+					return isPromise ? promise(value, decode) : decode(value);
+				}
+				const numCallbacks = callbacks.length;
+				try {
+					const value = fn(...args.map(arg));
+					afterCall();
+					return isPromise ? promise(value, decode) : decode(value);
+				} finally {
+					callbacks.length = numCallbacks;
+				}
+			},
+		}[name];
+	}
+
+	function canCompile() {
+		try {
+			new Function("");
+			return true;
+		} catch {
+			return false;
+		}
+	}
+
+	// Wrappers are compiled unless `compile` is false or code generation is disallowed
+	// (eg. by a Content Security Policy without `unsafe-eval`), in which case closures are used instead.
+	function runtimeWrappers() {
+		const wrapper = (compile ?? canCompile()) ? compiledWrapper : closureWrapper;
+		return Object.fromEntries(Array.from(manifest.exports, ([name, typeInfo]) => [name, wrapper(name, typeInfo)]));
+	}
+
+	// end: runtime wrappers
+
+	const wrappers = precompiled
+		? Object.fromEntries(Object.keys(precompiled.exports).map((name) => [name, precompiledWrapper(name)]))
+		: runtimeWrappers();
+
+	// Exports take precedence over the functions that wrap provides, which they would hide
+	const builtins = { allocArray, poll, scope, setLogLevel, stats };
+	for (const name of Object.keys(wrappers)) {
+		if (Object.hasOwn(builtins, name)) {
+			console.warn(`The export \`${name}\` hides the function of the same name provided by wrap; rename it to use both`);
+		}
+	}
+	return Object.assign(builtins, wrappers);
 }
 
+// begin: runtime wrappers
+
+// Return the source of a function that returns a wrapper for the given export (given the typed array constructors
+// in enum variant order), specialized using basic dead-code
+// elimination to elide unnecessary transformations (eg. only include Option-processing code if the return value
+// is an Option). Promises are decoded once the Rust-side future completes, so decoding is split into its own
+// function. Note: This is mirrored by `wrapper_source` in src/bin/to_js.rs, which generates the same code
+// ahead of time (as checked by its tests).
+function wrapperSource(name, typeInfo, numArgs, arrayTypes) {
+	const [isResult, isOption, isArray, arrayType, transformIndex, isPromise, isPinned, convertsArgs] = typeInfo;
+	const args = Array.from({ length: numArgs }, (_, i) => `x${i + 1}`);
+	const argsAsString = args.join(", ");
+	const needsPair = isResult || isOption || isArray;
+	const isView = isArray && transformIndex === 9;
+	const mayCopy = transformIndex < 7 || isView;
+	const result = mayCopy ? `(slice ? ret.slice() : ret)` : `ret`;
+	return `
+		function decode(value) {
+			${needsPair ? `let pair = u32Pair(value);` : ``}
+			${isResult ? `tryResult(pair);` : ``}
+			${isOption ? `if (tryOption(pair)) return null;` : ``}
+			${isPinned ? `const handle = value;
+			value = exports.to_js_pinned_value(handle);` : ``}
+			${isPinned && isArray ? `pair = u32Pair(value);` : ``}
+			${isArray ? `value = new ${arrayTypes[arrayType].name}(exports.memory.buffer, pair[0], pair[1])` : ``}
+			const ret = transform(value, arrayType);
+			${isView ? `if (track) trackView(ret);` : ``}
+			return ${isPinned ? `pinned(${result}, handle)` : result}
+		}
+		return function ${name}(${argsAsString}) {
+			if (arguments.length !== ${args.length}) {
+				throw new Error(\`${name}: expected ${args.length} argument${args.length === 1 ? '' : 's'}, got \${arguments.length}\`);
+			}
+			${convertsArgs ? `const numCallbacks = callbacks.length;
+			try {` : ``}
+			const value = exports[${JSON.stringify(name)}](${(convertsArgs ? args.map((x) => `arg(${x})`) : args).join(", ")});
+			afterCall();
+			return ${isPromise ? `promise(value, decode)` : `decode(value)`};
+			${convertsArgs ? `} finally {
+				callbacks.length = numCallbacks;
+			}` : ``}
+		}`;
+}
+
+// The source of the declarations that `wrap` depends on besides itself, for evaluating it in a worker
+function workerPrelude() {
+	return String(wrapperSource);
+}
+
+// end: runtime wrappers
+
 // Create a JavaScript-side class that corresponds to a Rust-side struct.
-export function createClass(
+function createClass(
 	// A WebAssembly instance wrapper returned by `wrap(instance)`
 	instance,
 	// Name prefix shared by all methods, separated from method names by an underscore
@@ -200,4 +913,140 @@ export function createClass(
 		};
 	}
 	return Class;
-}
\ No newline at end of file
+}
+
+// Instantiate a compiled WebAssembly.Module in a Web Worker, so that its #[js] functions run off the main thread.
+// Returns a promise for an object with the same functions as `wrap(instance)`, except that each one returns a
+// promise for its value, plus `terminate`, which stops the worker (rejecting the promises of unfinished calls, as
+// does an error in the worker). Values are decoded in the worker in the same way as by `wrap`, and are then copied
+// to the calling thread by structured cloning. Typed arrays are always copied out of WebAssembly memory (as if
+// `alwaysCopyData` were true), and their buffers are transferred rather than copied.
+// Since functions cannot be sent to a worker, functions that take callbacks or return pinned values or iterators,
+// and modules with #[js_import] functions, are not supported, nor are `allocArray` and `scope`.
+// `prelude` is the source of the declarations that `wrap` depends on, which the glue code generated by the to_js
+// binary replaces with that of its `precompiled` wrappers.
+function wrapWorker(module, prelude = workerPrelude()) {
+	// The worker evaluates the prelude and the source of `wrap`, followed by that of `workerMain`
+	const source = `${prelude}\n${wrap}\n(${workerMain})();\n`;
+	const url = URL.createObjectURL(new Blob([source], { type: "text/javascript" }));
+	const worker = new Worker(url, { type: "module" });
+	const pending = new Map();
+	let nextId = 0;
+	let terminated = false;
+
+	function call(name) {
+		return {
+			[name](...args) {
+				return new Promise((resolve, reject) => {
+					if (terminated) {
+						reject(new Error("wrapWorker: the worker has been terminated"));
+						return;
+					}
+					const id = nextId++;
+					pending.set(id, { resolve, reject });
+					worker.postMessage({ id, name, args });
+				});
+			},
+		}[name];
+	}
+
+	// Reject the promises of all unfinished calls, whose results will never arrive
+	function rejectPending(error) {
+		for (const { reject } of pending.values()) reject(error);
+		pending.clear();
+	}
+
+	function terminate() {
+		terminated = true;
+		worker.terminate();
+		rejectPending(new Error("wrapWorker: the worker has been terminated"));
+	}
+
+	return new Promise((resolve, reject) => {
+		worker.onerror = (event) => {
+			URL.revokeObjectURL(url);
+			reject(new Error(event.message));
+		};
+		// The first message reports whether the module was instantiated, and the names of its functions
+		worker.onmessage = ({ data }) => {
+			URL.revokeObjectURL(url);
+			worker.onerror = (event) => rejectPending(new Error(event.message));
+			if ("error" in data) {
+				worker.terminate();
+				reject(new Error(data.error));
+				return;
+			}
+			worker.onmessage = ({ data: { id, value, error } }) => {
+				const { resolve, reject } = pending.get(id);
+				pending.delete(id);
+				if (error === undefined) resolve(value);
+				else reject(new Error(error));
+			};
+			const functions = Object.fromEntries(data.names.map((name) => [name, call(name)]));
+			resolve(Object.assign(functions, { terminate }));
+		};
+		worker.postMessage({ module });
+	});
+}
+
+// The entry point of the worker created by `wrapWorker`, which runs in the worker alongside `wrap`
+function workerMain() {
+	let functions;
+	let memory;
+
+	addEventListener("message", async ({ data }) => {
+		if (data.module) {
+			try {
+				// Forward the module's `to_js` imports to the handlers installed by `wrap`
+				const handlers = {};
+				const to_js = {};
+				for (const { module: namespace, name } of WebAssembly.Module.imports(data.module)) {
+					if (namespace === "to_js") to_js[name] = (...args) => handlers[name](...args);
+				}
+				const instance = await WebAssembly.instantiate(data.module, { to_js });
+				// `precompiled` is only declared by the prelude of the generated glue code
+				const wrappers = typeof precompiled === "undefined" ? undefined : precompiled;
+				functions = wrap(instance, true, { handlers, precompiled: wrappers });
+				memory = instance.exports.memory;
+				// Unless they are hidden by exports of the same name
+				if (!instance.exports.allocArray) delete functions.allocArray;
+				if (!instance.exports.scope) delete functions.scope;
+				postMessage({ names: Object.keys(functions) });
+			} catch (e) {
+				postMessage({ error: String(e?.message ?? e) });
+			}
+			return;
+		}
+
+		const { id, name, args } = data;
+		try {
+			const buffers = new Set();
+			const value = detach(await functions[name](...args), buffers);
+			postMessage({ id, value }, Array.from(buffers));
+		} catch (e) {
+			postMessage({ id, error: String(e?.message ?? e) });
+		}
+	});
+
+	// Copy the typed arrays within a value that are views of WebAssembly memory (eg. in a ViewRef) or of part of a
+	// larger buffer, which structured cloning would copy whole, into buffers of their own, and collect the buffers of
+	// all of them to be transferred. Arrays and objects are copied rather than modified.
+	function detach(value, buffers) {
+		if (ArrayBuffer.isView(value)) {
+			const { buffer, byteOffset, byteLength } = value;
+			const isCopy = buffer instanceof ArrayBuffer && buffer !== memory.buffer;
+			if (!isCopy || byteOffset !== 0 || byteLength !== buffer.byteLength) {
+				const copy = new Uint8Array(buffer, byteOffset, byteLength).slice().buffer;
+				value = value instanceof DataView ? new DataView(copy) : new value.constructor(copy);
+			}
+			buffers.add(value.buffer);
+			return value;
+		}
+		if (Array.isArray(value)) return value.map((x) => detach(x, buffers));
+		// Plain objects and class instances (such as ViewRef), which are cloned as plain objects of their own properties
+		if (Object.prototype.toString.call(value) === "[object Object]") {
+			return Object.fromEntries(Object.entries(value).map(([key, x]) => [key, detach(x, buffers)]));
+		}
+		return value;
+	}
+}
@@ -6,14 +6,48 @@ pub use js_proc_macro::*;
 
 #[macro_use]
 mod typeinfo;
+pub mod console;
+mod executor;
+#[cfg(feature = "host")]
+pub mod host;
+#[doc(hidden)]
+pub mod manifest;
 mod niche;
+pub mod performance;
+mod ser;
+mod stats;
 mod types;
 
-pub use typeinfo::TypeInfo;
+pub use stats::{stats, Stats};
+#[doc(hidden)]
+pub use typeinfo::{ArgKind, NotConverted};
+pub use typeinfo::{Info, TypeInfo, ABI_VERSION};
+pub use types::iter::JsIter;
+pub use types::jsfn::{JsArgs, JsFn};
 #[cfg(feature = "json")]
 pub use types::json::Json;
-pub use types::keepalive::{clear_keepalive, KeepAlive};
+pub use types::keepalive::{clear_keepalive, CallFrame, JsCall, KeepAlive, PreserveKeepAlive};
+#[cfg(feature = "msgpack")]
+pub use types::msgpack::MsgPack;
+pub use types::owned::{FromOwned, Owned};
 pub use types::packed::*;
+pub use types::pinned::Pinned;
+pub use types::promise::JsPromise;
+#[cfg(feature = "structured")]
+pub use types::structured::{as_view, Structured};
+pub use types::viewref::{ViewRef, ViewSource};
+
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+pub use tracing;
+
+// Without the tracing feature, exported function calls are not wrapped in spans (see performance.rs)
+#[cfg(not(feature = "tracing"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! export_span {
+    ($name:ident) => {};
+}
 
 // Wasm is the central type of this library and represents values that can be returned across the FFI boundary.
 // Individual types that we want to be serializable implement Into<Wasm> via impls of the `From` trait.
@@ -48,13 +82,49 @@ impl<T: ToWasm> IntoWasm for T {
     }
 }
 
+// Values received from JavaScript as the return values of imported functions (see from_js!),
+// which are passed across the FFI boundary as plain f64s.
+pub trait FromWasm {
+    fn from_wasm(value: f64) -> Self;
+}
+
+// The companion exports that return the type info of each function, which are superseded by the manifest (see
+// manifest.rs). They are only included in builds with debug assertions, unless the info-exports feature is enabled
+// for hosts that cannot access the compiled WebAssembly.Module.
+#[cfg(any(debug_assertions, feature = "info-exports"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! info_export {
+    ($item:item) => {
+        $item
+    };
+}
+
+#[cfg(not(any(debug_assertions, feature = "info-exports")))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! info_export {
+    ($item:item) => {};
+}
+
+// Marks modules built without the companion exports, so that `wrap` can tell that it needs the manifest
+#[cfg(not(any(debug_assertions, feature = "info-exports")))]
+#[export_name = "to_js_no_info_exports"]
+extern "C" fn no_info_exports() {}
+
 /// This macro is part of the API surface of this package. The other part is the #[js] proc macro, which calls this one.
 /// You can wrap a series of function definitions in this macro in order to export them to JavaScript via WebAssembly.
 /// Note: Unlike the #[js] proc macro, to_js! requires that all functions have an explicit return type, even if it is (),
 /// since we use that macro capture ($ret) to figure out the TypeInfo for each function the user wants to export.
+/// Each exported function clears the values kept alive by previous calls (see KeepAlive) before it runs, unless the
+/// definitions are preceded by `@preserve_keepalive`, which is intended for functions that do not stash anything
+/// themselves, such as getters, so that they can be called without invalidating the results of previous calls.
 #[macro_export]
 macro_rules! to_js {
-    ($( $(#[$meta:meta])* $vis:vis fn $name:ident($($arg:ident : $typ:ty$(,)?)*) -> $ret:ty $body:block )*) => {
+    (@preserve_keepalive $($fns:tt)*) => {
+        $crate::to_js!(@export preserve $($fns)*);
+    };
+    (@export $keepalive:ident $( $(#[$meta:meta])* $vis:vis fn $name:ident($($arg:ident : $typ:ty$(,)?)*) -> $ret:ty $body:block )*) => {
         $(
             // Define the original function
             $(#[$meta])*
@@ -63,22 +133,122 @@ macro_rules! to_js {
             // Define exported functions, using a const block in order to allow repetition of the Rust-side
             // function names (call and info) if multiple functions are exported in the same outer scope.
             const _: () = {
-                use $crate::{IntoWasm, TypeInfo};
+                #[allow(unused_imports)]
+                use $crate::{IntoWasm, NotConverted, TypeInfo};
 
                 // Define the exported function, which returns an f64-encoded Wasm value
                 #[unsafe(export_name = concat!(stringify!($name)))]
                 pub extern "C" fn call($($arg: $typ),*) -> f64 {
-                    $crate::clear_keepalive();
+                    let _frame = $crate::CallFrame::enter();
+                    $crate::to_js!(@keepalive $keepalive $name);
+                    $crate::export_span!($name);
                     let value = $name($($arg),*);
                     value.into_wasm().value()
                 }
 
                 // Define a companion function which returns the info needed to interpret the encoding.
-                #[unsafe(export_name = concat!(stringify!($name), "_info_"))]
-                pub extern "C" fn type_info() -> f64 {
-                    let info = <$ret as TypeInfo>::type_info();
-                    info.into_wasm().value()
+                $crate::info_export! {
+                    #[unsafe(export_name = concat!(stringify!($name), "_info_"))]
+                    pub extern "C" fn type_info() -> f64 {
+                        // The type info of the return value, marked if any argument is converted by the wrapper
+                        let info = <$ret as TypeInfo>::type_info()
+                            .converts_args(false $(|| $crate::ArgKind::<$typ>::CONVERTED)*);
+                        info.into_wasm().value()
+                    }
+                }
+
+                // Describe the function in the manifest, as exported data and in the custom section (see manifest.rs)
+                #[cfg(target_arch = "wasm32")]
+                const ENTRY: [u8; $crate::manifest::entry_len(stringify!($name), 1)] =
+                    $crate::manifest::entry(
+                        $crate::manifest::EXPORT,
+                        stringify!($name),
+                        &[<$ret as TypeInfo>::INFO.converts_args(false $(|| $crate::ArgKind::<$typ>::CONVERTED)*)],
+                    );
+
+                #[cfg(target_arch = "wasm32")]
+                #[unsafe(export_name = concat!("to_js_manifest_export_", stringify!($name)))]
+                static MANIFEST_EXPORT: [u8; ENTRY.len()] = ENTRY;
+
+                #[cfg(target_arch = "wasm32")]
+                #[unsafe(link_section = "to_js_manifest")]
+                #[used]
+                static MANIFEST: [u8; ENTRY.len()] = ENTRY;
+            };
+        )*
+    };
+    (@export $($fns:tt)*) => {
+        compile_error!("to_js! expects function definitions with explicit return types");
+    };
+    (@keepalive clear $name:ident) => {
+        $crate::clear_keepalive();
+    };
+    (@keepalive preserve $name:ident) => {
+        // Check (in debug builds) that the function does not stash anything, since the stash is not cleared
+        let _preserve = $crate::PreserveKeepAlive::new(stringify!($name));
+    };
+    ($($fns:tt)*) => {
+        $crate::to_js!(@export clear $($fns)*);
+    };
+}
+
+/// The counterpart of to_js! for calling JavaScript functions from Rust. This is called by the #[js_import] proc macro.
+/// Each function declaration defines a Rust function of the same name that calls the JavaScript function provided
+/// for it at instantiation time. Arguments are encoded in the same way as return values from exported functions,
+/// and are decoded on the JavaScript side using the type info returned from a companion export.
+/// Note: As with to_js!, all functions must have an explicit return type, even if it is ().
+/// The functions are only defined on wasm32, since there is no JavaScript to call elsewhere, so native code that
+/// calls them (eg. in tests) must be gated on `target_arch = "wasm32"` as well.
+#[macro_export]
+macro_rules! from_js {
+    ($( $(#[$meta:meta])* $vis:vis fn $name:ident($($arg:ident : $typ:ty$(,)?)*) -> $ret:ty; )*) => {
+        $(
+            $(#[$meta])*
+            #[cfg(target_arch = "wasm32")]
+            $vis fn $name($($arg: $typ),*) -> $ret {
+                use $crate::{FromWasm, ToWasm};
+
+                #[link(wasm_import_module = "to_js")]
+                extern "C" {
+                    #[link_name = stringify!($name)]
+                    fn import($($arg: f64),*) -> f64;
                 }
+
+                let value = {
+                    let _call = $crate::JsCall::enter();
+                    unsafe { import($($arg.to_wasm().value()),*) }
+                };
+                <$ret as FromWasm>::from_wasm(value)
+            }
+
+            const _: () = {
+                #[allow(unused_imports)]
+                use $crate::{KeepAlive, IntoWasm, ToWasm, TypeInfo};
+
+                // Define a companion function which returns the info needed to decode each argument
+                // as a slice of f64-encoded Info values.
+                $crate::info_export! {
+                    #[unsafe(export_name = concat!("to_js_import_", stringify!($name)))]
+                    pub extern "C" fn type_info() -> f64 {
+                        let info: Vec<f64> = vec![$(<$typ as TypeInfo>::type_info().into_wasm().value()),*];
+                        KeepAlive::new(info).to_wasm().value()
+                    }
+                }
+
+                // Describe the function's arguments in the manifest, as exported data and in the custom section
+                // (see manifest.rs)
+                #[cfg(target_arch = "wasm32")]
+                const ENTRY: [u8; $crate::manifest::entry_len(stringify!($name), <[&str]>::len(&[$(stringify!($typ)),*]))] =
+                    $crate::manifest::entry($crate::manifest::IMPORT, stringify!($name), &[$(<$typ as TypeInfo>::INFO),*]);
+
+                #[cfg(target_arch = "wasm32")]
+                #[unsafe(export_name = concat!("to_js_manifest_import_", stringify!($name)))]
+                static MANIFEST_EXPORT: [u8; ENTRY.len()] = ENTRY;
+
+                #[cfg(target_arch = "wasm32")]
+                #[unsafe(link_section = "to_js_manifest")]
+                #[used]
+                static MANIFEST: [u8; ENTRY.len()] = ENTRY;
             };
         )*
     };
@@ -88,7 +258,9 @@ macro_rules! to_js {
 //
 
 /// Allocates a new box and forgets about this value, ceding ownership to JS.
+/// Live allocations are counted by type name (see `stats`).
 pub fn alloc<T>(x: T) -> *mut T {
+    stats::record_alloc::<T>();
     Box::into_raw(Box::new(x))
 }
 
@@ -98,18 +270,55 @@ pub fn alloc<T>(x: T) -> *mut T {
 /// Note: This is a function to take ownership rather than
 /// explicitly drop, which is more general and useful, but
 /// we call it `dealloc` to suggest its primary use case.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub fn dealloc<T>(ptr: *mut T) -> Box<T> {
+    stats::record_dealloc::<T>();
     unsafe { Box::from_raw(ptr) }
 }
 
+// The JavaScript glue code (lib.js) is embedded in two forms, which share the same bytes: an ES module that
+// exports `wrap`, `createClass`, and `wrapWorker`, and the body of a function that returns them. The function body
+// begins with a return statement, which works since function declarations are hoisted.
+macro_rules! script_header {
+    () => {
+        "\"use strict\";\nreturn { wrap, createClass, wrapWorker };\n"
+    };
+}
+
+macro_rules! module_footer {
+    () => {
+        "\nexport { wrap, createClass, wrapWorker };\n"
+    };
+}
+
+static GLUE: &str = concat!(
+    script_header!(),
+    include_str!(concat!(env!("OUT_DIR"), "/lib.js")),
+    module_footer!()
+);
+
 /// A trick: We embed most of the JavaScript required to use the compiled .wasm file inside of the file itself by
 /// exporting this constant directly. Its value is a two-element slice descriptor whose first element is a pointer
 /// to the string contents and whose second element is the string's length.
-/// Note: We currently embed unminified JavaScript. With basic zip compression, the difference in .wasm.zip size
-/// is a little over half a kilobyte between the unminified code and a version minified using esbuild:
-/// https://esbuild.github.io/try/#dAAwLjIzLjAALS1taW5pZnkA. (October 27, 2024.)
-/// Note: The JS code currently includes many comments, which take up space in the resulting bundle.
-/// Maybe one day Rust will allow removing comments in a static context.
+/// Note: By default, we embed unminified JavaScript, including its many comments. With the `minify` feature,
+/// release builds embed a copy with comments and unnecessary whitespace removed (see build.rs). With basic zip
+/// compression, the difference in .wasm.zip size is a little over half a kilobyte between the unminified code and
+/// a version minified using esbuild: https://esbuild.github.io/try/#dAAwLjIzLjAALS1taW5pZnkA. (October 27, 2024.)
 #[used]
 #[export_name = "JS"]
-pub static JS: &[u8] = &include_bytes!("./lib.js").as_slice();
+pub static JS: &[u8] = GLUE.as_bytes().split_at(script_header!().len()).1;
+
+/// The same JavaScript as `JS`, as the body of a function that returns `wrap`, `createClass`, and `wrapWorker`
+/// rather than as a module, so that it can be evaluated synchronously with `new Function` (see `wrapSync` in
+/// loader.js).
+#[used]
+#[export_name = "JS_SCRIPT"]
+pub static JS_SCRIPT: &[u8] = GLUE
+    .as_bytes()
+    .split_at(GLUE.len() - module_footer!().len())
+    .0;
+
+/// A loader for modules built with this crate (see loader.js), for use in Node and Deno, or wherever the module's
+/// glue code cannot be loaded from a Blob URL. Unlike `JS`, this is not embedded in the .wasm file; it is intended
+/// to be written out alongside it, eg. by a build script.
+pub const LOADER_JS: &str = include_str!("./loader.js");
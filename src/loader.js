@@ -0,0 +1,72 @@
+// Load a WebAssembly module built with to_js and return an object containing its #[js] exports, as returned by
+// `wrap` (with the glue code module available as `mod`). This works in Node and Deno as well as in browsers, and
+// does not rely on Blob URLs, which are unavailable in some environments and blocked by some Content Security Policies.
+//
+// The source may be a file path or URL (http(s) URLs are fetched, while paths and file URLs are read from disk),
+// a fetch Response, or the module's bytes as an ArrayBuffer or typed array. Options:
+// - alwaysCopyData: passed through to `wrap`
+// - imports: implementations of the functions imported via #[js_import]
+// - importObject: additional imports for the WebAssembly module, by namespace (other than `to_js`)
+export async function load(source, { alwaysCopyData = false, imports = {}, importObject = {} } = {}) {
+	const module = await WebAssembly.compile(await readBytes(source));
+
+	// Forward the module's `to_js` imports to handlers that decode their arguments, which are
+	// installed by `wrap` once the glue code has been loaded.
+	const handlers = {};
+	const to_js = {};
+	for (const { module: namespace, name } of WebAssembly.Module.imports(module)) {
+		if (namespace === "to_js") to_js[name] = (...args) => handlers[name](...args);
+	}
+	const instance = await WebAssembly.instantiate(module, { ...importObject, to_js });
+
+	const mod = await evaluate(instance);
+	return Object.assign(mod.wrap(instance, alwaysCopyData, { imports, handlers }), { mod });
+}
+
+// Synchronously wrap an already-instantiated module, returning an object containing its #[js] exports (with the
+// glue code module available as `mod`). The arguments are the same as those of `wrap`. This evaluates the glue
+// code with `new Function`, so it cannot be used where a Content Security Policy disallows `unsafe-eval`.
+export function wrapSync(instance, alwaysCopyData = false, options = {}) {
+	const mod = new Function(readGlueCode(instance, "JS_SCRIPT"))();
+	return Object.assign(mod.wrap(instance, alwaysCopyData, options), { mod });
+}
+
+async function readBytes(source) {
+	if (source instanceof ArrayBuffer || ArrayBuffer.isView(source)) return source;
+	if (typeof Response !== "undefined" && source instanceof Response) return source.arrayBuffer();
+	const url = source instanceof URL ? source : /^(https?|file):/.test(source) ? new URL(source) : null;
+	if (url !== null && url.protocol !== "file:") return (await fetch(url)).arrayBuffer();
+	const path = url ?? source;
+	if (typeof Deno !== "undefined") return Deno.readFile(path);
+	const { readFile } = await import("node:fs/promises");
+	return readFile(path);
+}
+
+// Read the glue code (lib.js) that the module embeds, which is located by the slice descriptor exported as `JS`
+// (as a module) or `JS_SCRIPT` (as a function body)
+function readGlueCode(instance, name) {
+	const { memory, [name]: descriptor } = instance.exports;
+	const view = new DataView(memory.buffer);
+	const ptr = view.getUint32(descriptor.value, true);
+	const len = view.getUint32(descriptor.value + 4, true);
+	return new TextDecoder().decode(new Uint8Array(memory.buffer, ptr, len));
+}
+
+// Evaluate the glue code as a module by importing it from a `data:` URL. If that is not possible (eg. because
+// `data:` URLs are blocked by a Content Security Policy), evaluate it as the body of a function instead.
+async function evaluate(instance) {
+	try {
+		return await import(`data:text/javascript;base64,${base64(readGlueCode(instance, "JS"))}`);
+	} catch {
+		return new Function(readGlueCode(instance, "JS_SCRIPT"))();
+	}
+}
+
+function base64(code) {
+	const bytes = new TextEncoder().encode(code);
+	let binary = "";
+	for (let i = 0; i < bytes.length; i += 0x8000) {
+		binary += String.fromCharCode(...bytes.subarray(i, i + 0x8000));
+	}
+	return btoa(binary);
+}
@@ -0,0 +1,62 @@
+// The manifest describes the type info of every #[js] export and #[js_import] import in a single table, so that
+// the JavaScript side does not need to call a companion export for each function (and so that the companion
+// exports can be omitted, as they are in builds without debug assertions).
+//
+// Each to_js! and from_js! invocation writes one entry per function into two statics:
+// - One exported as `to_js_manifest_export_<name>` (or `to_js_manifest_import_<name>`), which the linker exports as a
+//   global holding its address in memory, so that `wrap` can read the entry through the instance's exports.
+// - One in the `to_js_manifest` link section. On wasm32, link sections become custom sections, and the linker
+//   concatenates the entries of all crates into a single `to_js_manifest` custom section, from which the to_js binary
+//   reads them without instantiating the module. Custom sections are not loaded into memory, so `wrap` cannot read it
+//   from an instance.
+//
+// Entry layout (all integers little-endian):
+// - kind: u8 (EXPORT or IMPORT)
+// - name length: u32
+// - name: UTF-8 bytes
+// - info count: u8 (1 for exports, which describe the return value; the number of arguments for imports)
+// - info: 8 bytes each, in the same encoding as the U8Octet returned by an `_info_` export
+
+use crate::typeinfo::Info;
+
+pub const EXPORT: u8 = 0;
+pub const IMPORT: u8 = 1;
+
+pub const fn entry_len(name: &str, count: usize) -> usize {
+    1 + 4 + name.len() + 1 + 8 * count
+}
+
+pub const fn entry<const N: usize>(kind: u8, name: &str, infos: &[Info]) -> [u8; N] {
+    assert!(N == entry_len(name, infos.len()));
+    assert!(infos.len() <= u8::MAX as usize);
+    let mut out = [0; N];
+    out[0] = kind;
+    let name_len = (name.len() as u32).to_le_bytes();
+    let mut i = 0;
+    while i < 4 {
+        out[1 + i] = name_len[i];
+        i += 1;
+    }
+    let name = name.as_bytes();
+    let mut pos = 5;
+    i = 0;
+    while i < name.len() {
+        out[pos] = name[i];
+        pos += 1;
+        i += 1;
+    }
+    out[pos] = infos.len() as u8;
+    pos += 1;
+    i = 0;
+    while i < infos.len() {
+        let bytes = infos[i].to_bytes();
+        let mut j = 0;
+        while j < 8 {
+            out[pos] = bytes[j];
+            pos += 1;
+            j += 1;
+        }
+        i += 1;
+    }
+    out
+}
@@ -18,6 +18,7 @@ pub(crate) enum Niche {
 }
 
 impl Niche {
+    #[allow(clippy::new_ret_no_self, clippy::wrong_self_convention)]
     pub(crate) fn new(self, x: u32) -> Wasm {
         const SIGNALING_NAN: u64 = 0xfff80000_00000000;
         match self {
@@ -0,0 +1,264 @@
+#![cfg(feature = "tracing")]
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::SetGlobalDefaultError;
+use tracing::{Event, Metadata, Subscriber};
+
+// A tracing subscriber that records spans using the browser's Performance API via a function imported from the
+// `to_js` namespace, which the JavaScript side provides by default. Entering a span notes the current time and
+// exiting it calls `performance.measure`, so spans show up in the timings track of browser profilers.
+// Events are recorded with `performance.mark`. When this feature is enabled, every exported function call is
+// wrapped in a span named after the export, which has no effect unless a subscriber is installed.
+pub struct PerformanceSubscriber {
+    next_id: AtomicU64,
+    // Names and reference counts of open spans
+    spans: Mutex<HashMap<u64, (&'static str, usize)>>,
+}
+
+// What to record (mirrored on the JavaScript side)
+#[cfg_attr(test, derive(Debug, PartialEq))]
+enum Kind {
+    Enter,
+    Exit,
+    Event,
+}
+
+impl PerformanceSubscriber {
+    pub fn new() -> Self {
+        PerformanceSubscriber {
+            next_id: AtomicU64::new(1),
+            spans: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn name(&self, id: &Id) -> &'static str {
+        self.spans.lock().unwrap()[&id.into_u64()].0
+    }
+}
+
+impl Default for PerformanceSubscriber {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Installs a `PerformanceSubscriber` as the global default subscriber.
+pub fn init_tracing() -> Result<(), SetGlobalDefaultError> {
+    tracing::subscriber::set_global_default(PerformanceSubscriber::new())
+}
+
+impl Subscriber for PerformanceSubscriber {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let name = span.metadata().name();
+        self.spans.lock().unwrap().insert(id, (name, 1));
+        Id::from_u64(id)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut visitor = MessageVisitor(None);
+        event.record(&mut visitor);
+        let message = visitor
+            .0
+            .unwrap_or_else(|| event.metadata().name().to_string());
+        record(Kind::Event, 0, &message);
+    }
+
+    fn enter(&self, span: &Id) {
+        record(Kind::Enter, span.into_u64(), self.name(span));
+    }
+
+    fn exit(&self, span: &Id) {
+        record(Kind::Exit, span.into_u64(), self.name(span));
+    }
+
+    fn clone_span(&self, id: &Id) -> Id {
+        if let Some((_, refs)) = self.spans.lock().unwrap().get_mut(&id.into_u64()) {
+            *refs += 1;
+        }
+        id.clone()
+    }
+
+    fn try_close(&self, id: Id) -> bool {
+        let mut spans = self.spans.lock().unwrap();
+        let id = id.into_u64();
+        let Some((_, refs)) = spans.get_mut(&id) else {
+            return false;
+        };
+        *refs -= 1;
+        if *refs == 0 {
+            spans.remove(&id);
+            return true;
+        }
+        false
+    }
+}
+
+// Extracts the message of an event
+struct MessageVisitor(Option<String>);
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = Some(value.to_string());
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn record(kind: Kind, id: u64, name: &str) {
+    #[link(wasm_import_module = "to_js")]
+    extern "C" {
+        // The id is passed as an i64, which JavaScript receives as a BigInt, so that it is never truncated
+        fn to_js_performance(kind: u32, id: u64, ptr: *const u8, len: usize);
+    }
+
+    unsafe { to_js_performance(kind as u32, id, name.as_ptr(), name.len()) }
+}
+
+#[cfg(all(not(target_arch = "wasm32"), not(test)))]
+fn record(_kind: Kind, _id: u64, _name: &str) {}
+
+// In tests, record to a list that they can check
+#[cfg(all(not(target_arch = "wasm32"), test))]
+fn record(kind: Kind, id: u64, name: &str) {
+    tests::RECORDED.with_borrow_mut(|recorded| recorded.push((kind, id, name.to_string())));
+}
+
+// Opens a span for the duration of an exported function call, named after the export.
+// The macro is selected based on whether this feature is enabled when compiling this crate,
+// since a #[cfg] in the body of to_js! would be evaluated in the context of the calling crate.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! export_span {
+    ($name:ident) => {
+        let _span = $crate::tracing::info_span!(stringify!($name)).entered();
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use tracing::Dispatch;
+
+    thread_local! {
+        pub(super) static RECORDED: RefCell<Vec<(Kind, u64, String)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn recorded() -> Vec<(Kind, u64, String)> {
+        RECORDED.take()
+    }
+
+    fn record(kind: Kind, id: u64, name: &str) -> (Kind, u64, String) {
+        (kind, id, name.to_string())
+    }
+
+    // Runs `f` with a PerformanceSubscriber whose first span id does not fit in 32 bits, returning the number of
+    // spans left open
+    fn with_subscriber(f: impl FnOnce()) -> usize {
+        let subscriber = PerformanceSubscriber::new();
+        subscriber.next_id.store(1 << 32, Ordering::Relaxed);
+        let dispatch = Dispatch::new(subscriber);
+        tracing::dispatcher::with_default(&dispatch, f);
+        let subscriber = dispatch.downcast_ref::<PerformanceSubscriber>().unwrap();
+        let open = subscriber.spans.lock().unwrap().len();
+        open
+    }
+
+    #[test]
+    fn spans_are_measured_between_enter_and_exit() {
+        let open = with_subscriber(|| {
+            let outer = tracing::info_span!("outer");
+            let copy = outer.clone();
+            outer.in_scope(|| {
+                let _inner = tracing::info_span!("inner").entered();
+            });
+            drop(outer);
+            let _entered = copy.entered();
+        });
+        assert_eq!(open, 0);
+        let outer = 1 << 32;
+        let inner = outer + 1;
+        assert_eq!(
+            recorded(),
+            [
+                record(Kind::Enter, outer, "outer"),
+                record(Kind::Enter, inner, "inner"),
+                record(Kind::Exit, inner, "inner"),
+                record(Kind::Exit, outer, "outer"),
+                record(Kind::Enter, outer, "outer"),
+                record(Kind::Exit, outer, "outer"),
+            ]
+        );
+    }
+
+    #[test]
+    fn spans_stay_open_until_every_reference_is_closed() {
+        let dispatch = Dispatch::new(PerformanceSubscriber::new());
+        let subscriber = dispatch.downcast_ref::<PerformanceSubscriber>().unwrap();
+        let open = || subscriber.spans.lock().unwrap().len();
+        tracing::dispatcher::with_default(&dispatch, || {
+            let span = tracing::info_span!("span");
+            let copy = span.clone();
+            drop(span);
+            assert_eq!(open(), 1);
+            drop(copy);
+            assert_eq!(open(), 0);
+        });
+        // Closing an unknown span does nothing
+        assert!(!subscriber.try_close(Id::from_u64(1)));
+    }
+
+    #[test]
+    fn events_are_marked_with_their_message() {
+        with_subscriber(|| {
+            tracing::info!("generated {} values", 3);
+            tracing::info!(count = 3);
+        });
+        let [(kind, id, message), (_, _, name)] = <[_; 2]>::try_from(recorded()).unwrap();
+        assert_eq!(
+            (kind, id, message.as_str()),
+            (Kind::Event, 0, "generated 3 values")
+        );
+        // Events without a message are marked with the name of the event
+        assert!(name.starts_with("event src/performance.rs:"), "{name}");
+    }
+
+    #[test]
+    fn exports_are_wrapped_in_spans() {
+        let open = with_subscriber(|| {
+            crate::export_span!(add);
+            tracing::info!("adding");
+        });
+        assert_eq!(open, 0);
+        let id = 1 << 32;
+        assert_eq!(
+            recorded(),
+            [
+                record(Kind::Enter, id, "add"),
+                record(Kind::Event, 0, "adding"),
+                record(Kind::Exit, id, "add"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,1263 @@
+#![cfg(feature = "structured")]
+
+use crate::typeinfo::ArrayType;
+use serde::ser::{self, Impossible, Serialize};
+use std::fmt::Display;
+
+/// A serde serializer that encodes values as a compact stream of ops, which the JavaScript side decodes
+/// into plain objects and arrays. Sequences of numbers of a single type are not encoded as ops but copied
+/// into a separate 8-byte aligned data section and referenced from the op stream by offset, so that they
+/// can be returned as typed array views into WebAssembly memory. Slices serialized with `as_view` are not
+/// copied at all, but referenced from the op stream by pointer (see `VIEW_NAME`).
+/// Tuples are always encoded as arrays, as with serde_json.
+///
+/// The encoded buffer consists of an 8-byte header containing the byte lengths of the data section and
+/// the op stream as two little-endian u32s, followed by the data section, followed by the op stream.
+/// Each op is a tag byte followed by its payload; the ops and their tags are mirrored on the JavaScript side.
+#[derive(Default)]
+pub(crate) struct Serializer {
+    ops: Vec<u8>,
+    data: Vec<u8>,
+}
+
+// Op tags (mirrored on the JavaScript side)
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const F64: u8 = 3; // f64 payload
+const U64: u8 = 4; // u64 payload, decoded as BigInt
+const I64: u8 = 5; // i64 payload, decoded as BigInt
+const STRING: u8 = 6; // u32 byte length, followed by UTF-8 bytes
+const ARRAY: u8 = 7; // u32 count, followed by `count` values
+const OBJECT: u8 = 8; // u32 count, followed by `count` key-value pairs
+const TYPED_ARRAY: u8 = 9; // u8 ArrayType, u32 byte offset into the data section, u32 element count
+const VIEW: u8 = 10; // u8 ArrayType, u32 pointer into WebAssembly memory, u32 element count
+
+/// The name of the newtype struct that `as_view` serializes a slice as, which this serializer encodes as a
+/// VIEW op that refers to the slice's elements by pointer. Other serializers serialize the slice as usual.
+pub(crate) const VIEW_NAME: &str = "$to_js::View";
+
+// Largest integer that can be represented exactly as an f64.
+// Integers with a larger magnitude are encoded as BigInts.
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+impl Serializer {
+    /// Serializes a value into a vector of 8-byte words, returning the
+    /// vector together with the byte length of the encoded contents.
+    pub(crate) fn encode(x: &impl Serialize) -> Result<(Vec<u64>, usize)> {
+        let mut ser = Serializer::default();
+        x.serialize(&mut ser)?;
+        let Serializer { ops, data } = ser;
+        let mut bytes = Vec::with_capacity(8 + data.len() + ops.len());
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(ops.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&data);
+        bytes.extend_from_slice(&ops);
+        let len = bytes.len();
+        bytes.resize(len.next_multiple_of(8), 0);
+        let words = bytes
+            .chunks_exact(8)
+            .map(|chunk| u64::from_ne_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Ok((words, len))
+    }
+
+    fn op(&mut self, tag: u8) {
+        self.ops.push(tag);
+    }
+
+    fn u32(&mut self, x: u32) {
+        self.ops.extend_from_slice(&x.to_le_bytes());
+    }
+
+    fn str(&mut self, s: &str) {
+        self.op(STRING);
+        self.u32(s.len() as u32);
+        self.ops.extend_from_slice(s.as_bytes());
+    }
+
+    // Write a container op with a placeholder count, returning the
+    // position of the count so that it can be patched at the end.
+    fn begin(&mut self, tag: u8) -> usize {
+        self.op(tag);
+        let pos = self.ops.len();
+        self.u32(0);
+        pos
+    }
+
+    fn end(&mut self, pos: usize, count: u32) {
+        self.ops[pos..pos + 4].copy_from_slice(&count.to_le_bytes());
+    }
+
+    // Begin a typed array in the data section, returning its offset
+    fn begin_data(&mut self) -> usize {
+        let offset = self.data.len().next_multiple_of(8);
+        self.data.resize(offset, 0);
+        offset
+    }
+
+    fn typed_array(&mut self, array_type: ArrayType, offset: usize, count: u32) {
+        self.op(TYPED_ARRAY);
+        self.ops.push(array_type as u8);
+        self.u32(offset as u32);
+        self.u32(count);
+    }
+
+    fn view(&mut self, array_type: ArrayType, ptr: *const u8, count: u32) {
+        self.op(VIEW);
+        self.ops.push(array_type as u8);
+        self.u32(ptr as usize as u32);
+        self.u32(count);
+    }
+}
+
+/// A number captured while serializing a sequence, which is buffered in order to
+/// determine whether the sequence can be encoded as a typed array.
+#[derive(Copy, Clone)]
+enum Num {
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+impl Num {
+    fn array_type(self) -> ArrayType {
+        match self {
+            Num::U8(_) => ArrayType::U8,
+            Num::I8(_) => ArrayType::I8,
+            Num::U16(_) => ArrayType::U16,
+            Num::I16(_) => ArrayType::I16,
+            Num::U32(_) => ArrayType::U32,
+            Num::I32(_) => ArrayType::I32,
+            Num::F32(_) => ArrayType::F32,
+            Num::U64(_) => ArrayType::U64,
+            Num::I64(_) => ArrayType::I64,
+            Num::F64(_) => ArrayType::F64,
+        }
+    }
+
+    // Read a number of the given type that was written with write_le
+    fn read_le(array_type: ArrayType, bytes: &[u8]) -> Num {
+        match array_type {
+            ArrayType::U8 => Num::U8(u8::from_le_bytes(bytes.try_into().unwrap())),
+            ArrayType::I8 => Num::I8(i8::from_le_bytes(bytes.try_into().unwrap())),
+            ArrayType::U16 => Num::U16(u16::from_le_bytes(bytes.try_into().unwrap())),
+            ArrayType::I16 => Num::I16(i16::from_le_bytes(bytes.try_into().unwrap())),
+            ArrayType::U32 => Num::U32(u32::from_le_bytes(bytes.try_into().unwrap())),
+            ArrayType::I32 => Num::I32(i32::from_le_bytes(bytes.try_into().unwrap())),
+            ArrayType::F32 => Num::F32(f32::from_le_bytes(bytes.try_into().unwrap())),
+            ArrayType::U64 => Num::U64(u64::from_le_bytes(bytes.try_into().unwrap())),
+            ArrayType::I64 => Num::I64(i64::from_le_bytes(bytes.try_into().unwrap())),
+            ArrayType::F64 | ArrayType::None => {
+                Num::F64(f64::from_le_bytes(bytes.try_into().unwrap()))
+            }
+        }
+    }
+
+    fn write_le(self, out: &mut Vec<u8>) {
+        match self {
+            Num::U8(x) => out.extend_from_slice(&x.to_le_bytes()),
+            Num::I8(x) => out.extend_from_slice(&x.to_le_bytes()),
+            Num::U16(x) => out.extend_from_slice(&x.to_le_bytes()),
+            Num::I16(x) => out.extend_from_slice(&x.to_le_bytes()),
+            Num::U32(x) => out.extend_from_slice(&x.to_le_bytes()),
+            Num::I32(x) => out.extend_from_slice(&x.to_le_bytes()),
+            Num::F32(x) => out.extend_from_slice(&x.to_le_bytes()),
+            Num::U64(x) => out.extend_from_slice(&x.to_le_bytes()),
+            Num::I64(x) => out.extend_from_slice(&x.to_le_bytes()),
+            Num::F64(x) => out.extend_from_slice(&x.to_le_bytes()),
+        }
+    }
+
+    // Write this number as a standalone op
+    fn write_op(self, ser: &mut Serializer) {
+        let x = match self {
+            Num::U8(x) => x as f64,
+            Num::I8(x) => x as f64,
+            Num::U16(x) => x as f64,
+            Num::I16(x) => x as f64,
+            Num::U32(x) => x as f64,
+            Num::I32(x) => x as f64,
+            Num::F32(x) => x as f64,
+            Num::F64(x) => x,
+            Num::U64(x) if x <= MAX_SAFE_INTEGER => x as f64,
+            Num::I64(x) if x.unsigned_abs() <= MAX_SAFE_INTEGER => x as f64,
+            Num::U64(x) => {
+                ser.op(U64);
+                ser.ops.extend_from_slice(&x.to_le_bytes());
+                return;
+            }
+            Num::I64(x) => {
+                ser.op(I64);
+                ser.ops.extend_from_slice(&x.to_le_bytes());
+                return;
+            }
+        };
+        ser.op(F64);
+        ser.ops.extend_from_slice(&x.to_le_bytes());
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Seq<'a>;
+    type SerializeTuple = Seq<'a>;
+    type SerializeTupleStruct = Seq<'a>;
+    type SerializeTupleVariant = Seq<'a>;
+    type SerializeMap = Map<'a>;
+    type SerializeStruct = Map<'a>;
+    type SerializeStructVariant = Map<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.op(if v { TRUE } else { FALSE });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        Num::I8(v).write_op(self);
+        Ok(())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        Num::I16(v).write_op(self);
+        Ok(())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        Num::I32(v).write_op(self);
+        Ok(())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        Num::I64(v).write_op(self);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        Num::U8(v).write_op(self);
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        Num::U16(v).write_op(self);
+        Ok(())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        Num::U32(v).write_op(self);
+        Ok(())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        Num::U64(v).write_op(self);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        Num::F32(v).write_op(self);
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        Num::F64(v).write_op(self);
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.str(v.encode_utf8(&mut [0; 4]));
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.str(v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        let offset = self.begin_data();
+        self.data.extend_from_slice(v);
+        self.typed_array(ArrayType::U8, offset, v.len() as u32);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.op(NULL);
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.str(variant);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        if name == VIEW_NAME {
+            if let Ok(Some((array_type, ptr, count))) = value.serialize(ViewProbe) {
+                self.view(array_type, ptr, count);
+                return Ok(());
+            }
+        }
+        value.serialize(self)
+    }
+
+    // Enum variants with data are externally tagged, ie. encoded as { variant: value }
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let pos = self.begin(OBJECT);
+        self.end(pos, 1);
+        self.str(variant);
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Seq<'a>> {
+        Ok(Seq::new(self, true))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Seq<'a>> {
+        Ok(Seq::new(self, false))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Seq<'a>> {
+        Ok(Seq::new(self, false))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Seq<'a>> {
+        let pos = self.begin(OBJECT);
+        self.end(pos, 1);
+        self.str(variant);
+        Ok(Seq::new(self, false))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Map<'a>> {
+        Ok(Map::new(self))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Map<'a>> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Map<'a>> {
+        let pos = self.begin(OBJECT);
+        self.end(pos, 1);
+        self.str(variant);
+        self.serialize_map(Some(len))
+    }
+}
+
+/// Sequence serializer. Numbers are written straight into the data section for as long as all elements are
+/// numbers of the same type, in which case the sequence is encoded as a typed array. Otherwise, the sequence falls
+/// back to being encoded as an array op, and any numbers written so far are moved into the op stream.
+pub(crate) struct Seq<'a> {
+    ser: &'a mut Serializer,
+    // Whether the sequence may be encoded as a typed array (which tuples are not)
+    typed: bool,
+    // The type of the numbers written so far, and the data section length before and after padding
+    numbers: Option<(ArrayType, usize, usize)>,
+    // Position of the array op count, once we've fallen back to encoding an array op
+    pos: Option<usize>,
+    count: u32,
+}
+
+impl<'a> Seq<'a> {
+    fn new(ser: &'a mut Serializer, typed: bool) -> Self {
+        Seq {
+            ser,
+            typed,
+            numbers: None,
+            pos: None,
+            count: 0,
+        }
+    }
+
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.count += 1;
+        if self.pos.is_none() {
+            if self.typed && self.number(value) {
+                return Ok(());
+            }
+            self.begin_array();
+        }
+        value.serialize(&mut *self.ser)
+    }
+
+    // Write the value to the data section if it is a number of the same type as the previous elements
+    fn number<T: ?Sized + Serialize>(&mut self, value: &T) -> bool {
+        let Ok(num) = value.serialize(Probe) else {
+            return false;
+        };
+        match self.numbers {
+            None => {
+                let start = self.ser.data.len();
+                let offset = self.ser.begin_data();
+                self.numbers = Some((num.array_type(), start, offset));
+            }
+            Some((array_type, ..)) if array_type as u8 == num.array_type() as u8 => {}
+            Some(_) => return false,
+        }
+        num.write_le(&mut self.ser.data);
+        true
+    }
+
+    // Fall back to encoding an array op, moving any numbers out of the data section
+    fn begin_array(&mut self) {
+        self.pos = Some(self.ser.begin(ARRAY));
+        if let Some((array_type, start, offset)) = self.numbers.take() {
+            let data = self.ser.data.split_off(offset);
+            self.ser.data.truncate(start);
+            for bytes in data.chunks_exact(array_type.size()) {
+                Num::read_le(array_type, bytes).write_op(self.ser);
+            }
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match (self.pos, self.numbers) {
+            (Some(pos), _) => self.ser.end(pos, self.count),
+            (None, Some((array_type, _, offset))) => {
+                self.ser.typed_array(array_type, offset, self.count)
+            }
+            (None, None) => {
+                let pos = self.ser.begin(ARRAY);
+                self.ser.end(pos, 0);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for Seq<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for Seq<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for Seq<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for Seq<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+/// Map and struct serializer. Keys may be of any type; they are converted to strings on the JavaScript side.
+pub(crate) struct Map<'a> {
+    ser: &'a mut Serializer,
+    pos: usize,
+    count: u32,
+}
+
+impl<'a> Map<'a> {
+    fn new(ser: &'a mut Serializer) -> Self {
+        let pos = ser.begin(OBJECT);
+        Map { ser, pos, count: 0 }
+    }
+}
+
+impl ser::SerializeMap for Map<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        self.count += 1;
+        key.serialize(&mut *self.ser)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<()> {
+        self.ser.end(self.pos, self.count);
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for Map<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.count += 1;
+        self.ser.str(key);
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for Map<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeMap::end(self)
+    }
+}
+
+/// A serializer that succeeds only for primitive numbers, returning them as a `Num`.
+/// Used to determine whether a sequence element can be part of a typed array.
+struct Probe;
+
+impl ser::Serializer for Probe {
+    type Ok = Num;
+    type Error = Error;
+    type SerializeSeq = Impossible<Num, Error>;
+    type SerializeTuple = Impossible<Num, Error>;
+    type SerializeTupleStruct = Impossible<Num, Error>;
+    type SerializeTupleVariant = Impossible<Num, Error>;
+    type SerializeMap = Impossible<Num, Error>;
+    type SerializeStruct = Impossible<Num, Error>;
+    type SerializeStructVariant = Impossible<Num, Error>;
+
+    fn serialize_i8(self, v: i8) -> Result<Num> {
+        Ok(Num::I8(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Num> {
+        Ok(Num::I16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Num> {
+        Ok(Num::I32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Num> {
+        Ok(Num::I64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Num> {
+        Ok(Num::U8(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Num> {
+        Ok(Num::U16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Num> {
+        Ok(Num::U32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Num> {
+        Ok(Num::U64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Num> {
+        Ok(Num::F32(v))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Num> {
+        Ok(Num::F64(v))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Num> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<Num> {
+        Err(not_a_number())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Num> {
+        Err(not_a_number())
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Num> {
+        Err(not_a_number())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Num> {
+        Err(not_a_number())
+    }
+
+    fn serialize_none(self) -> Result<Num> {
+        Err(not_a_number())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<Num> {
+        Err(not_a_number())
+    }
+
+    fn serialize_unit(self) -> Result<Num> {
+        Err(not_a_number())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Num> {
+        Err(not_a_number())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Num> {
+        Err(not_a_number())
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Num> {
+        Err(not_a_number())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Err(not_a_number())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(not_a_number())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(not_a_number())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(not_a_number())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(not_a_number())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(not_a_number())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(not_a_number())
+    }
+}
+
+fn not_a_number() -> Error {
+    Error(String::new())
+}
+
+/// A serializer for the slice wrapped by `as_view`, which returns the type, address, and length of its elements
+/// if they are numbers (and None if the slice is empty). Since `as_view` serializes the elements of a slice by
+/// reference, their addresses are those of the elements in the slice.
+struct ViewProbe;
+
+struct ViewSeq {
+    view: Option<(ArrayType, *const u8, u32)>,
+}
+
+impl ser::SerializeSeq for ViewSeq {
+    type Ok = Option<(ArrayType, *const u8, u32)>;
+    type Error = Error;
+
+    // Each element must be a number whose address follows that of the previous one, and typed arrays also
+    // require their elements to be aligned
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        let array_type = value.serialize(Probe)?.array_type();
+        let ptr = value as *const T as *const u8;
+        let size = array_type.size();
+        if std::mem::size_of_val(value) != size || !(ptr as usize).is_multiple_of(size) {
+            return Err(not_a_number());
+        }
+        match &mut self.view {
+            None => self.view = Some((array_type, ptr, 1)),
+            Some((first_type, first, count))
+                if *first_type as u8 == array_type as u8
+                    && ptr == first.wrapping_add(*count as usize * size) =>
+            {
+                *count += 1
+            }
+            Some(_) => return Err(not_a_number()),
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(self.view)
+    }
+}
+
+impl ser::Serializer for ViewProbe {
+    type Ok = Option<(ArrayType, *const u8, u32)>;
+    type Error = Error;
+    type SerializeSeq = ViewSeq;
+    type SerializeTuple = Impossible<Self::Ok, Error>;
+    type SerializeTupleStruct = Impossible<Self::Ok, Error>;
+    type SerializeTupleVariant = Impossible<Self::Ok, Error>;
+    type SerializeMap = Impossible<Self::Ok, Error>;
+    type SerializeStruct = Impossible<Self::Ok, Error>;
+    type SerializeStructVariant = Impossible<Self::Ok, Error>;
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<ViewSeq> {
+        Ok(ViewSeq { view: None })
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_i8(self, _v: i8) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_i16(self, _v: i16) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_i32(self, _v: i32) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_i64(self, _v: i64) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_u8(self, _v: u8) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_u16(self, _v: u16) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_u32(self, _v: u32) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_u64(self, _v: u64) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_char(self, _v: char) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_str(self, _v: &str) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<Self::Ok> {
+        Err(not_a_number())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
+        Err(not_a_number())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(not_a_number())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(not_a_number())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(not_a_number())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct> {
+        Err(not_a_number())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(not_a_number())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use std::collections::BTreeMap;
+
+    // A decoded value, mirroring what the JavaScript side produces (with typed arrays and views left undecoded)
+    #[derive(Debug, PartialEq)]
+    enum Value {
+        Null,
+        Bool(bool),
+        Number(f64),
+        BigInt(i128),
+        String(std::string::String),
+        Array(Vec<Value>),
+        Object(Vec<(Value, Value)>),
+        TypedArray(u8, Vec<u8>),
+        View(u8, u32, u32),
+    }
+
+    use Value::*;
+
+    fn decode(x: impl Serialize) -> Value {
+        let (words, len) = Serializer::encode(&x).unwrap();
+        let bytes: Vec<u8> = words
+            .iter()
+            .flat_map(|w| w.to_ne_bytes())
+            .take(len)
+            .collect();
+        let data_len = u32::from_le_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        let ops_len = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        assert_eq!(8 + data_len + ops_len, len);
+        let mut decoder = Decoder {
+            data: &bytes[8..8 + data_len],
+            ops: &bytes[8 + data_len..],
+        };
+        let value = decoder.read();
+        assert!(decoder.ops.is_empty(), "trailing ops");
+        value
+    }
+
+    struct Decoder<'a> {
+        data: &'a [u8],
+        ops: &'a [u8],
+    }
+
+    impl Decoder<'_> {
+        fn take(&mut self, n: usize) -> &[u8] {
+            let (head, tail) = self.ops.split_at(n);
+            self.ops = tail;
+            head
+        }
+
+        fn u32(&mut self) -> u32 {
+            u32::from_le_bytes(self.take(4).try_into().unwrap())
+        }
+
+        fn u64(&mut self) -> [u8; 8] {
+            self.take(8).try_into().unwrap()
+        }
+
+        fn read(&mut self) -> Value {
+            match self.take(1)[0] {
+                NULL => Null,
+                FALSE => Bool(false),
+                TRUE => Bool(true),
+                F64 => Number(f64::from_le_bytes(self.u64())),
+                U64 => BigInt(u64::from_le_bytes(self.u64()) as i128),
+                I64 => BigInt(i64::from_le_bytes(self.u64()) as i128),
+                STRING => {
+                    let len = self.u32() as usize;
+                    String(std::str::from_utf8(self.take(len)).unwrap().to_string())
+                }
+                ARRAY => Array((0..self.u32()).map(|_| self.read()).collect()),
+                OBJECT => Object(
+                    (0..self.u32())
+                        .map(|_| (self.read(), self.read()))
+                        .collect(),
+                ),
+                TYPED_ARRAY => {
+                    let array_type = self.take(1)[0];
+                    let offset = self.u32() as usize;
+                    let size = [1, 1, 2, 2, 4, 4, 4, 8, 8, 8][array_type as usize];
+                    assert_eq!(offset % 8, 0, "typed arrays are 8-byte aligned");
+                    let len = self.u32() as usize * size;
+                    TypedArray(array_type, self.data[offset..offset + len].to_vec())
+                }
+                VIEW => View(self.take(1)[0], self.u32(), self.u32()),
+                op => panic!("unknown op {op}"),
+            }
+        }
+    }
+
+    fn s(x: &str) -> Value {
+        String(x.to_string())
+    }
+
+    fn bytes<T: Copy>(x: &[T]) -> Vec<u8> {
+        let len = std::mem::size_of_val(x);
+        unsafe { std::slice::from_raw_parts(x.as_ptr() as *const u8, len) }.to_vec()
+    }
+
+    #[test]
+    fn scalars() {
+        assert_eq!(decode(()), Null);
+        assert_eq!(decode(Option::<u8>::None), Null);
+        assert_eq!(decode(Some(true)), Bool(true));
+        assert_eq!(decode(false), Bool(false));
+        assert_eq!(decode(7u8), Number(7.0));
+        assert_eq!(decode(-1.5f32), Number(-1.5));
+        assert_eq!(decode('x'), s("x"));
+        assert_eq!(decode("héllo"), s("héllo"));
+    }
+
+    #[test]
+    fn large_integers_are_bigints() {
+        assert_eq!(decode((1u64 << 53) - 1), Number(((1u64 << 53) - 1) as f64));
+        assert_eq!(decode(1u64 << 53), BigInt(1 << 53));
+        assert_eq!(decode(u64::MAX), BigInt(u64::MAX as i128));
+        assert_eq!(decode(i64::MIN), BigInt(i64::MIN as i128));
+        assert_eq!(
+            decode(-(1i64 << 53) + 1),
+            Number((-(1i64 << 53) + 1) as f64)
+        );
+    }
+
+    #[test]
+    fn numeric_sequences_are_typed_arrays() {
+        assert_eq!(
+            decode(vec![1u32, 2, 3]),
+            TypedArray(4, bytes(&[1u32, 2, 3]))
+        );
+        assert_eq!(
+            decode(&[0.5f64, 0.25][..]),
+            TypedArray(9, bytes(&[0.5f64, 0.25]))
+        );
+        assert_eq!(decode(vec![-1i8]), TypedArray(1, bytes(&[-1i8])));
+        assert_eq!(decode(Vec::<f64>::new()), Array(vec![]));
+    }
+
+    #[test]
+    fn mixed_sequences_are_arrays() {
+        assert_eq!(
+            decode(vec![Some(1u8), None, Some(3)]),
+            Array(vec![Number(1.0), Null, Number(3.0)])
+        );
+        // Numbers of different types, after some have already been written to the data section
+        #[derive(Serialize)]
+        #[serde(untagged)]
+        enum Num {
+            U8(u8),
+            F64(f64),
+        }
+        assert_eq!(
+            decode(vec![Num::U8(1), Num::U8(2), Num::F64(0.5)]),
+            Array(vec![Number(1.0), Number(2.0), Number(0.5)])
+        );
+        assert_eq!(decode(vec![vec![1u8], vec![]]), {
+            Array(vec![TypedArray(0, vec![1]), Array(vec![])])
+        });
+    }
+
+    #[test]
+    fn falling_back_removes_numbers_from_the_data_section() {
+        #[derive(Serialize)]
+        #[serde(untagged)]
+        enum Item {
+            Num(u16),
+            Str(&'static str),
+        }
+        let (words, len) = Serializer::encode(&vec![Item::Num(1), Item::Str("x")]).unwrap();
+        let bytes: Vec<u8> = words
+            .iter()
+            .flat_map(|w| w.to_ne_bytes())
+            .take(len)
+            .collect();
+        assert_eq!(u32::from_le_bytes(bytes[0..4].try_into().unwrap()), 0);
+    }
+
+    #[test]
+    fn tuples_are_arrays() {
+        #[derive(Serialize)]
+        struct Point(f64, f64);
+        #[derive(Serialize)]
+        enum Shape {
+            Line(u8, u8),
+        }
+        assert_eq!(decode((1u32, 2u32)), Array(vec![Number(1.0), Number(2.0)]));
+        // Serde serializes fixed-size arrays as tuples
+        assert_eq!(decode([1u8, 2]), Array(vec![Number(1.0), Number(2.0)]));
+        assert_eq!(
+            decode(Point(0.5, 1.5)),
+            Array(vec![Number(0.5), Number(1.5)])
+        );
+        assert_eq!(
+            decode(Shape::Line(1, 2)),
+            Object(vec![(s("Line"), Array(vec![Number(1.0), Number(2.0)]))])
+        );
+    }
+
+    #[test]
+    fn bytes_are_uint8_arrays() {
+        struct Bytes(&'static [u8]);
+        impl Serialize for Bytes {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                s: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                s.serialize_bytes(self.0)
+            }
+        }
+        assert_eq!(decode(Bytes(b"abc")), TypedArray(0, b"abc".to_vec()));
+        assert_eq!(decode(Bytes(b"")), TypedArray(0, vec![]));
+    }
+
+    #[test]
+    fn structs_maps_and_enums() {
+        #[derive(Serialize)]
+        struct Series {
+            name: &'static str,
+            values: Vec<f64>,
+            unit: Unit,
+            range: Option<Range>,
+        }
+        #[derive(Serialize)]
+        enum Unit {
+            Ms,
+        }
+        #[derive(Serialize)]
+        enum Range {
+            Between { min: u8, max: u8 },
+        }
+        assert_eq!(
+            decode(Series {
+                name: "latency",
+                values: vec![0.5],
+                unit: Unit::Ms,
+                range: Some(Range::Between { min: 0, max: 9 }),
+            }),
+            Object(vec![
+                (s("name"), s("latency")),
+                (s("values"), TypedArray(9, bytes(&[0.5f64]))),
+                (s("unit"), s("Ms")),
+                (
+                    s("range"),
+                    Object(vec![(
+                        s("Between"),
+                        Object(vec![(s("min"), Number(0.0)), (s("max"), Number(9.0))])
+                    )])
+                ),
+            ])
+        );
+        let map = BTreeMap::from([(1u8, "a"), (2, "b")]);
+        assert_eq!(
+            decode(map),
+            Object(vec![(Number(1.0), s("a")), (Number(2.0), s("b"))])
+        );
+    }
+
+    #[test]
+    fn as_view_refers_to_slices_by_pointer() {
+        #[derive(Serialize)]
+        struct Series {
+            #[serde(serialize_with = "crate::as_view")]
+            values: Vec<f64>,
+            #[serde(serialize_with = "crate::as_view")]
+            window: [f32; 2],
+            #[serde(serialize_with = "crate::as_view")]
+            empty: Vec<u16>,
+            #[serde(serialize_with = "crate::as_view")]
+            labels: Vec<&'static str>,
+        }
+        let series = Series {
+            values: vec![1.0, 2.0],
+            window: [0.0, 1.0],
+            empty: vec![],
+            labels: vec!["a"],
+        };
+        let values = series.values.as_ptr() as usize as u32;
+        let window = series.window.as_ptr() as usize as u32;
+        assert_eq!(
+            decode(&series),
+            Object(vec![
+                (s("values"), View(9, values, 2)),
+                (s("window"), View(6, window, 2)),
+                // Slices that are empty, or whose elements are not numbers, are serialized as usual
+                (s("empty"), Array(vec![])),
+                (s("labels"), Array(vec![s("a")])),
+            ])
+        );
+    }
+}
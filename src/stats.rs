@@ -0,0 +1,169 @@
+// Statistics about memory held on behalf of JavaScript, for diagnosing leaks: values kept alive by KeepAlive,
+// objects allocated with `alloc` and not yet deallocated, and the total size of WebAssembly memory.
+// These are available to Rust via `stats()` and to JavaScript via `rs.stats()`.
+
+use crate::types::keepalive::keepalive_stats;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Stats {
+    /// Number of values currently kept alive by KeepAlive
+    pub keepalive_count: usize,
+    /// Approximate number of bytes currently kept alive by KeepAlive
+    pub keepalive_bytes: usize,
+    /// Largest number of values that have been kept alive at once
+    pub peak_keepalive_count: usize,
+    /// Largest number of bytes that have been kept alive at once
+    pub peak_keepalive_bytes: usize,
+    /// Number of live objects allocated with `alloc`, by type name
+    pub allocs: Vec<(&'static str, usize)>,
+    /// Size of WebAssembly memory in 64 KiB pages (0 on other targets)
+    pub memory_pages: usize,
+}
+
+/// Number of live objects allocated with `alloc`, by type name.
+/// This is a global rather than a thread-local since objects may be deallocated on a different thread.
+static ALLOCS: Mutex<BTreeMap<&'static str, usize>> = Mutex::new(BTreeMap::new());
+
+pub(crate) fn record_alloc<T>() {
+    *ALLOCS
+        .lock()
+        .unwrap()
+        .entry(std::any::type_name::<T>())
+        .or_default() += 1;
+}
+
+pub(crate) fn record_dealloc<T>() {
+    let mut allocs = ALLOCS.lock().unwrap();
+    let name = std::any::type_name::<T>();
+    if let Some(count) = allocs.get_mut(name) {
+        *count -= 1;
+        if *count == 0 {
+            allocs.remove(name);
+        }
+    }
+}
+
+pub fn stats() -> Stats {
+    let keepalive = keepalive_stats();
+    let allocs = ALLOCS.lock().unwrap();
+
+    #[cfg(target_arch = "wasm32")]
+    let memory_pages = core::arch::wasm32::memory_size(0);
+    #[cfg(not(target_arch = "wasm32"))]
+    let memory_pages = 0;
+
+    Stats {
+        keepalive_count: keepalive.len,
+        keepalive_bytes: keepalive.size,
+        peak_keepalive_count: keepalive.peak_len,
+        peak_keepalive_bytes: keepalive.peak_size,
+        allocs: allocs.iter().map(|(&name, &count)| (name, count)).collect(),
+        memory_pages,
+    }
+}
+
+impl Stats {
+    /// Encodes the stats as a JSON object with camelCase keys, without requiring serde
+    fn to_json(&self) -> String {
+        let mut json = String::new();
+        write!(
+            json,
+            r#"{{"keepaliveCount":{},"keepaliveBytes":{},"peakKeepaliveCount":{},"peakKeepaliveBytes":{},"memoryPages":{},"allocs":{{"#,
+            self.keepalive_count,
+            self.keepalive_bytes,
+            self.peak_keepalive_count,
+            self.peak_keepalive_bytes,
+            self.memory_pages,
+        )
+        .unwrap();
+        for (i, (name, count)) in self.allocs.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            // Type names do not contain quotes or backslashes, but escape them just in case
+            let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+            write!(json, r#"{separator}"{name}":{count}"#).unwrap();
+        }
+        json.push_str("}}");
+        json
+    }
+}
+
+thread_local! {
+    // The JSON-encoded stats are stored here rather than in the KeepAlive stash so that retrieving them
+    // neither clears the stash nor adds to it.
+    static STATS_JSON: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+// Export used by the JavaScript side to retrieve the stats, which are returned as a (ptr, len) pair of a JSON string
+#[unsafe(export_name = "to_js_stats")]
+extern "C" fn stats_json() -> f64 {
+    use crate::ToWasm;
+    STATS_JSON.with_borrow_mut(|json| {
+        *json = stats().to_json();
+        json.as_str().to_wasm().value()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::keepalive::keep_alive;
+    use crate::{alloc, clear_keepalive, dealloc};
+
+    #[test]
+    fn keepalive_stats() {
+        keep_alive(vec![0u32; 4]);
+        keep_alive(String::with_capacity(10));
+        let before = stats();
+        assert_eq!(before.keepalive_count, 2);
+        let vec_size = std::mem::size_of::<Vec<u32>>() + 16;
+        let string_size = std::mem::size_of::<String>() + 10;
+        assert_eq!(before.keepalive_bytes, vec_size + string_size);
+
+        clear_keepalive();
+        keep_alive(1u8);
+        let after = stats();
+        assert_eq!((after.keepalive_count, after.keepalive_bytes), (1, 1));
+        assert_eq!(after.peak_keepalive_count, 2);
+        assert_eq!(after.peak_keepalive_bytes, vec_size + string_size);
+    }
+
+    #[test]
+    fn allocs_by_type_name() {
+        struct Tracked;
+        let name = std::any::type_name::<Tracked>();
+        let count = || {
+            stats()
+                .allocs
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, count)| *count)
+        };
+
+        let ptrs = [alloc(Tracked), alloc(Tracked)];
+        assert_eq!(count(), Some(2));
+        drop(dealloc(ptrs[0]));
+        assert_eq!(count(), Some(1));
+        drop(dealloc(ptrs[1]));
+        assert_eq!(count(), None);
+    }
+
+    #[test]
+    fn json() {
+        let stats = Stats {
+            keepalive_count: 1,
+            keepalive_bytes: 2,
+            peak_keepalive_count: 3,
+            peak_keepalive_bytes: 4,
+            allocs: vec![("a::B", 5), ("c::D<\"e\">", 6)],
+            memory_pages: 7,
+        };
+        assert_eq!(
+            stats.to_json(),
+            r#"{"keepaliveCount":1,"keepaliveBytes":2,"peakKeepaliveCount":3,"peakKeepaliveBytes":4,"memoryPages":7,"allocs":{"a::B":5,"c::D<\"e\">":6}}"#
+        );
+    }
+}
@@ -2,7 +2,20 @@
 //
 
 use crate::ToWasm;
-use crate::{U8Octet, Wasm};
+use crate::{FromOwned, JsFn, Owned, U8Octet, Wasm};
+use std::marker::PhantomData;
+
+/// The version of the encoding of values and type info shared by Rust and the JavaScript glue code (and any other
+/// host that decodes values returned from the module). It is returned by the `to_js_abi_version` export and checked
+/// by `wrap`, and must be incremented whenever the layout of `Info`, the order of `ArrayType` or `Transform`, or the
+/// encoding of any value (including the niches of `Option` and `Result`) changes. See "ABI" in the README.
+/// The decoders in lib.js and host.py declare the version they decode, which is checked by the tests below.
+pub const ABI_VERSION: u32 = 2;
+
+#[export_name = "to_js_abi_version"]
+extern "C" fn abi_version() -> u32 {
+    ABI_VERSION
+}
 
 // TypedArray type if the return value is to be converted to a typed array
 // Variant order is mirrored in an array on the JavaScript side.
@@ -21,6 +34,19 @@ pub enum ArrayType {
     None,
 }
 
+#[cfg(any(feature = "structured", feature = "host"))]
+impl ArrayType {
+    // Size of each element in bytes
+    pub(crate) const fn size(self) -> usize {
+        match self {
+            ArrayType::U8 | ArrayType::I8 | ArrayType::None => 1,
+            ArrayType::U16 | ArrayType::I16 => 2,
+            ArrayType::U32 | ArrayType::I32 | ArrayType::F32 => 4,
+            ArrayType::U64 | ArrayType::I64 | ArrayType::F64 => 8,
+        }
+    }
+}
+
 // Transformation function to be used before returning the value to the caller
 // Variant order is mirrored in an array on the JavaScript side.
 #[derive(Copy, Clone)]
@@ -43,29 +69,45 @@ pub enum Transform {
     Bool,
     String,
     Json,
+    MsgPack,
+    Structured,
+    Iter,
+    ViewRef,
 }
 
+// The info needed to decode a value, which is passed to JavaScript as the 8 bytes of a U8Octet (see to_bytes):
+// [is_result, is_option, is_array, array_type, transform, is_promise, is_pinned, converts_args]
+// where flags are 0 or 1, and array_type and transform are the discriminants of ArrayType and Transform.
+// converts_args describes the function rather than the value, and is only set in the info of exports (see ArgKind).
+// This layout is part of the ABI (see ABI_VERSION).
+#[derive(Copy, Clone)]
 pub struct Info {
     array_type: ArrayType,
     transform: Transform,
     is_array: bool,
     is_option: bool,
     is_result: bool,
+    is_promise: bool,
+    is_pinned: bool,
+    converts_args: bool,
 }
 
-// Helper functions to upgrade a basic type into an array, option, and/or result.
+// Helper functions to upgrade a basic type into an array, option, result, promise, and/or pinned value.
 impl Info {
-    pub fn new(array_type: ArrayType, is_array: bool, transform: Transform) -> Self {
+    pub const fn new(array_type: ArrayType, is_array: bool, transform: Transform) -> Self {
         Self {
             array_type,
             transform,
             is_array,
             is_option: false,
             is_result: false,
+            is_promise: false,
+            is_pinned: false,
+            converts_args: false,
         }
     }
 
-    pub(crate) fn array(self) -> Info {
+    pub(crate) const fn array(self) -> Info {
         // If array_type is None, then this type cannot be placed into a (typed) array.
         // An example of this is U32Pair; we cannot ship &[U32Pair] across the FFI boundary.
         debug_assert!(!matches!(self.array_type, ArrayType::None));
@@ -76,7 +118,7 @@ impl Info {
         }
     }
 
-    pub(crate) fn option(self) -> Info {
+    pub(crate) const fn option(self) -> Info {
         debug_assert!(!self.is_option);
         Info {
             is_option: true,
@@ -84,7 +126,7 @@ impl Info {
         }
     }
 
-    pub(crate) fn result(self) -> Info {
+    pub(crate) const fn result(self) -> Info {
         debug_assert!(!self.is_result);
         Info {
             is_result: true,
@@ -92,7 +134,57 @@ impl Info {
         }
     }
 
-    pub(crate) fn identity_transform(self) -> Info {
+    pub(crate) const fn promise(self) -> Info {
+        debug_assert!(!self.is_promise);
+        Info {
+            is_promise: true,
+            ..self
+        }
+    }
+
+    pub(crate) const fn pinned(self) -> Info {
+        // The handle to a pinned value uses its own niche, so the value cannot itself be an Option or Result
+        debug_assert!(!self.is_option && !self.is_result && !self.is_pinned);
+        Info {
+            is_pinned: true,
+            ..self
+        }
+    }
+
+    pub(crate) const fn iter(self) -> Info {
+        // Iterators are returned in chunks, which are typed arrays
+        debug_assert!(!matches!(self.array_type, ArrayType::None));
+        debug_assert!(!self.is_array);
+        Info {
+            transform: Transform::Iter,
+            ..self
+        }
+    }
+
+    pub(crate) const fn view_ref(self) -> Info {
+        // Only typed arrays (which are returned with the identity transform) can be re-derived from memory. This is
+        // checked in release builds too, since INFO is a constant and the check fails the build rather than the call.
+        assert!(
+            self.is_array && matches!(self.transform, Transform::Identity),
+            "ViewRef can only wrap values that are returned as typed arrays"
+        );
+        Info {
+            transform: Transform::ViewRef,
+            ..self
+        }
+    }
+
+    // Mark the info of an exported function that takes arguments which the JavaScript wrapper converts (see ArgKind).
+    // This is called by the export macros.
+    #[doc(hidden)]
+    pub const fn converts_args(self, converts_args: bool) -> Info {
+        Info {
+            converts_args,
+            ..self
+        }
+    }
+
+    pub(crate) const fn identity_transform(self) -> Info {
         Info {
             transform: Transform::Identity,
             ..self
@@ -100,35 +192,45 @@ impl Info {
     }
 }
 
-impl ToWasm for Info {
-    fn to_wasm(&self) -> Wasm {
-        U8Octet([
+impl Info {
+    // The byte encoding of the info, which is passed to JavaScript as a U8Octet and stored in the manifest
+    pub(crate) const fn to_bytes(self) -> [u8; 8] {
+        [
             self.is_result as u8,
             self.is_option as u8,
             self.is_array as u8,
             self.array_type as u8,
             self.transform as u8,
-            0,
-            0,
-            0,
-        ])
-        .to_wasm()
+            self.is_promise as u8,
+            self.is_pinned as u8,
+            self.converts_args as u8,
+        ]
+    }
+}
+
+impl ToWasm for Info {
+    fn to_wasm(&self) -> Wasm {
+        U8Octet(self.to_bytes()).to_wasm()
     }
 }
 
 // Trait representing the ability to get type info for a type.
 // Every type that implements Wasm should implement this trait.
+// The info is a constant so that it can be written into the manifest at compile time (see manifest.rs), so
+// implementations define INFO; type_info() is kept (as a provided method) for existing callers.
 pub trait TypeInfo {
-    fn type_info() -> Info;
+    const INFO: Info;
+
+    fn type_info() -> Info {
+        Self::INFO
+    }
 }
 
 // The type info for a reference is the same as the typeinfo for the value.
 // References are Wasm-ified assuming the memory they refer to will live at
 // least past the FFI boundary (after the Rust function returns).
 impl<T: TypeInfo> TypeInfo for &T {
-    fn type_info() -> Info {
-        <T as TypeInfo>::type_info()
-    }
+    const INFO: Info = <T as TypeInfo>::INFO;
 }
 
 #[macro_export]
@@ -136,10 +238,45 @@ macro_rules! impl_typeinfo {
     ($( [$type:ty, $array_type:expr, $is_array:ident, $transform:expr] $(,)? )*) => {
         $(
             impl $crate::typeinfo::TypeInfo for $type {
-                fn type_info() -> $crate::typeinfo::Info {
-                    $crate::typeinfo::Info::new($array_type, $is_array, $transform)
-                }
+                const INFO: $crate::typeinfo::Info =
+                    $crate::typeinfo::Info::new($array_type, $is_array, $transform);
             }
         )*
     };
 }
+
+// Determines whether the JavaScript wrapper of an exported function needs to convert its arguments, which is the case
+// for JavaScript functions passed as JsFn (which are registered in a table for the duration of the call) and arrays
+// passed as Owned (which are moved to Rust). Wrappers of other functions pass their arguments through unchanged.
+// This relies on inherent associated constants taking precedence over trait ones: the export macros evaluate
+// `ArgKind::<T>::CONVERTED` for each argument type, which resolves to an inherent constant for the converted types
+// and to the NotConverted default for every other type.
+#[doc(hidden)]
+pub struct ArgKind<T: ?Sized>(PhantomData<T>);
+
+impl<Args, R> ArgKind<JsFn<'_, Args, R>> {
+    pub const CONVERTED: bool = true;
+}
+
+impl<T: FromOwned> ArgKind<Owned<T>> {
+    pub const CONVERTED: bool = true;
+}
+
+#[doc(hidden)]
+pub trait NotConverted {
+    const CONVERTED: bool = false;
+}
+
+impl<T: ?Sized> NotConverted for ArgKind<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoders_have_the_same_abi_version() {
+        let version = ABI_VERSION;
+        assert!(include_str!("lib.js").contains(&format!("const ABI_VERSION = {version};")));
+        assert!(include_str!("host.py").contains(&format!("\nABI_VERSION = {version}\n")));
+    }
+}
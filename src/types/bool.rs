@@ -1,6 +1,6 @@
 use crate::niche::{HasNiche, Niche};
 use crate::typeinfo::{ArrayType, Transform};
-use crate::{ToWasm, Wasm};
+use crate::{FromWasm, ToWasm, Wasm};
 
 // ToWasm impl
 //
@@ -11,6 +11,15 @@ impl ToWasm for bool {
     }
 }
 
+// FromWasm impl
+//
+
+impl FromWasm for bool {
+    fn from_wasm(value: f64) -> Self {
+        value != 0.0
+    }
+}
+
 // HasNiche impl
 //
 
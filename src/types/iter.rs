@@ -0,0 +1,146 @@
+use crate::niche::{HasNiche, Niche};
+use crate::typeinfo::{Info, TypeInfo};
+use crate::types::number::Number;
+use crate::{alloc, clear_keepalive, dealloc, CallFrame, IntoWasm, KeepAlive};
+use crate::{ToWasm, Wasm};
+use std::marker::PhantomData;
+
+// Represents an iterator over numbers that is returned to JavaScript as an iterable object, which pulls
+// elements from Rust in chunks (as typed arrays) rather than materializing all of them at once.
+// The iterator is kept alive in a heap-allocated handle until it is exhausted or JavaScript stops iterating.
+// Note: This is generic over the item type rather than the iterator type so that the return type of
+// an exported function can be named even when the iterator type cannot (eg. if it contains closures).
+pub struct JsIter<T>(Wasm, PhantomData<T>);
+
+// Type-erased iterator stored behind a handle
+trait NextChunk {
+    fn next_chunk(&mut self) -> Wasm;
+}
+
+struct Chunks<I> {
+    iter: I,
+    chunk_size: usize,
+}
+
+impl<T, I> NextChunk for Chunks<I>
+where
+    I: Iterator<Item = T>,
+    T: 'static,
+    for<'a> &'a Vec<T>: IntoWasm,
+{
+    fn next_chunk(&mut self) -> Wasm {
+        let chunk: Vec<T> = self.iter.by_ref().take(self.chunk_size).collect();
+        KeepAlive::new(chunk).to_wasm()
+    }
+}
+
+type Handle = *mut Box<dyn NextChunk>;
+
+impl<T> JsIter<T>
+where
+    T: 'static,
+    for<'a> &'a Vec<T>: IntoWasm,
+{
+    pub const DEFAULT_CHUNK_SIZE: usize = 4096;
+
+    pub fn new(iter: impl Iterator<Item = T> + 'static) -> JsIter<T> {
+        Self::with_chunk_size(iter, Self::DEFAULT_CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(
+        iter: impl Iterator<Item = T> + 'static,
+        chunk_size: usize,
+    ) -> JsIter<T> {
+        assert!(chunk_size > 0, "chunk size must be nonzero");
+        let chunks: Box<dyn NextChunk> = Box::new(Chunks { iter, chunk_size });
+        JsIter(alloc(chunks).to_wasm(), PhantomData)
+    }
+}
+
+// Exports used by the JavaScript side to pull chunks from an iterator, which are returned as (ptr, len) pairs.
+// An empty chunk signals that the iterator is exhausted, after which JavaScript drops the handle.
+// Each chunk is kept alive only until the next one is pulled, so JavaScript copies chunks out of memory unless the
+// caller opts into views (see `iterate` in lib.js).
+
+#[unsafe(export_name = "to_js_iter_next")]
+extern "C" fn iter_next(handle: Handle) -> f64 {
+    let _frame = CallFrame::enter();
+    clear_keepalive();
+    let chunks = unsafe { &mut *handle };
+    chunks.next_chunk().value()
+}
+
+#[unsafe(export_name = "to_js_iter_drop")]
+extern "C" fn iter_drop(handle: Handle) {
+    dealloc(handle);
+}
+
+// ToWasm impl
+//
+
+impl<T> ToWasm for JsIter<T> {
+    fn to_wasm(&self) -> Wasm {
+        self.0.clone()
+    }
+}
+
+// HasNiche impl
+//
+
+impl<T> HasNiche for JsIter<T> {
+    const N: Niche = Niche::HighBitsNaN;
+}
+
+// TypeInfo impl
+//
+
+impl<T: Number + TypeInfo> TypeInfo for JsIter<T> {
+    const INFO: Info = T::INFO.iter();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live_handles() -> usize {
+        let name = std::any::type_name::<Box<dyn NextChunk>>();
+        let allocs = crate::stats().allocs;
+        allocs
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    fn new_handle(iter: impl Iterator<Item = u32> + 'static, chunk_size: usize) -> Handle {
+        let chunks: Box<dyn NextChunk> = Box::new(Chunks { iter, chunk_size });
+        alloc(chunks)
+    }
+
+    // The number of elements in the next chunk, which is the high half of its (ptr, len) pair (only the length is
+    // meaningful on non-wasm32 targets)
+    fn next_len(handle: Handle) -> u64 {
+        iter_next(handle).to_bits() >> 32
+    }
+
+    // A single test, since the allocation counts are shared between threads
+    #[test]
+    fn chunks_are_pulled_until_exhausted_or_dropped() {
+        let before = live_handles();
+        let handle = new_handle(0..5, 2);
+        assert_eq!(live_handles(), before + 1);
+        assert_eq!(next_len(handle), 2);
+        assert_eq!(next_len(handle), 2);
+        assert_eq!(next_len(handle), 1);
+        // An exhausted iterator keeps returning empty chunks until it is dropped
+        assert_eq!(next_len(handle), 0);
+        assert_eq!(next_len(handle), 0);
+        iter_drop(handle);
+        assert_eq!(live_handles(), before);
+
+        // Iteration can end before the iterator is exhausted
+        let handle = new_handle(0.., 3);
+        assert_eq!(next_len(handle), 3);
+        iter_drop(handle);
+        assert_eq!(live_handles(), before);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::typeinfo::TypeInfo;
+use crate::{FromWasm, IntoWasm, ToWasm};
+use std::marker::PhantomData;
+
+// Represents a JavaScript function passed as an argument to an exported function. The JavaScript side registers
+// the function in a table for the duration of the call and passes its index, through which Rust can invoke it
+// with arguments encoded in the same way as return values (and decoded using their type info).
+// The function can only be called during the call to the exported function that received it, since its index is
+// reused by later calls, which the lifetime enforces: it is inferred to be that of the call unless the argument
+// is explicitly declared as JsFn<'static, ...>.
+#[repr(transparent)]
+pub struct JsFn<'a, Args, R = ()>(u32, PhantomData<fn(Args) -> R>, PhantomData<&'a ()>);
+
+impl<Args: JsArgs, R: FromWasm> JsFn<'_, Args, R> {
+    // JavaScript functions can only be called when running in a JavaScript host, so this is only defined on wasm32
+    #[cfg(target_arch = "wasm32")]
+    pub fn call(&self, args: Args) -> R {
+        let encoded = args.encode();
+        R::from_wasm(invoke(self.0, &encoded))
+    }
+}
+
+impl<Args, R> Clone for JsFn<'_, Args, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Args, R> Copy for JsFn<'_, Args, R> {}
+
+// All calls to JavaScript functions go through a single imported trampoline, which receives the index
+// of the function and a slice of (value, type info) pairs for its arguments.
+#[cfg(target_arch = "wasm32")]
+fn invoke(index: u32, args: &[f64]) -> f64 {
+    #[link(wasm_import_module = "to_js")]
+    extern "C" {
+        fn to_js_invoke(index: u32, ptr: *const f64, len: usize) -> f64;
+    }
+
+    let _call = crate::JsCall::enter();
+    unsafe { to_js_invoke(index, args.as_ptr(), args.len() / 2) }
+}
+
+// Tuples of arguments that can be passed to a JsFn
+pub trait JsArgs {
+    // Encode the arguments as interleaved (value, type info) pairs
+    fn encode(&self) -> Vec<f64>;
+}
+
+macro_rules! impl_js_args {
+    ($( ($($arg:ident),*) )*) => {
+        $(
+            impl<$($arg: ToWasm + TypeInfo),*> JsArgs for ($($arg,)*) {
+                #[allow(non_snake_case)]
+                fn encode(&self) -> Vec<f64> {
+                    let ($($arg,)*) = self;
+                    vec![$($arg.to_wasm().value(), $arg::type_info().into_wasm().value()),*]
+                }
+            }
+        )*
+    };
+}
+
+impl_js_args! {
+    ()
+    (A)
+    (A, B)
+    (A, B, C)
+    (A, B, C, D)
+    (A, B, C, D, E)
+    (A, B, C, D, E, F)
+    (A, B, C, D, E, F, G)
+    (A, B, C, D, E, F, G, H)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typeinfo::{ArgKind, Info, NotConverted};
+    use crate::Owned;
+
+    crate::to_js! {
+        fn takes_callback(n: u32, _cb: JsFn<(u32,), bool>) -> u32 {
+            n
+        }
+    }
+
+    #[test]
+    fn marks_functions_that_convert_args() {
+        let converted = [
+            ArgKind::<JsFn<(u32,), bool>>::CONVERTED,
+            ArgKind::<JsFn<'static, ()>>::CONVERTED,
+            ArgKind::<Owned<Vec<f64>>>::CONVERTED,
+            ArgKind::<u32>::CONVERTED,
+            ArgKind::<*const u8>::CONVERTED,
+        ];
+        assert_eq!(converted, [true, true, true, false, false]);
+
+        let info: Info = <u32 as TypeInfo>::INFO;
+        assert_eq!(info.to_bytes()[7], 0);
+        assert_eq!(info.converts_args(true).to_bytes()[7], 1);
+        assert_eq!(takes_callback(1, JsFn(0, PhantomData, PhantomData)), 1);
+    }
+}
@@ -1,13 +1,131 @@
 use crate::niche::{HasNiche, Niche};
 use crate::typeinfo::{Info, TypeInfo};
 use crate::{IntoWasm, ToWasm, Wasm};
+use std::any::Any;
+use std::cell::RefCell;
 use std::marker::PhantomData;
-use std::sync::RwLock;
 
-/// Global stash to keep values alive across FFI boundary until the next FFI call,
-/// storing them in a vector of type-erased boxes, to be dropped when the next value is put in.
-/// This is a Vec so more than one value can be stashed during the execution of a single JS call.
-static KEEPALIVE: RwLock<Vec<Box<dyn Send + Sync + 'static>>> = RwLock::new(Vec::new());
+/// Stash to keep values alive across FFI boundary until the next FFI call.
+/// Values are stored in a vector of type-erased boxes, except for byte buffers (Vec<u8> and String),
+/// which are common enough to be stored unboxed in a vector of their own.
+/// These are Vecs so more than one value can be stashed during the execution of a single JS call.
+/// The stash is thread-local, so values need not be Send or Sync, and no locking is required.
+/// On wasm32 without threads this is equivalent to a global, while on threaded targets each
+/// thread stashes (and clears) its own values.
+/// Each value is tagged with the generation of the call that stashed it, which is the number of exported
+/// calls that enclosed that call. If Rust calls into JavaScript, which calls back into an export, the nested
+/// call only clears values from its own generation (and any deeper ones), leaving those of the outer calls alive.
+/// A trap (or a JavaScript exception) unwinds the WebAssembly stack without running destructors, so the call depth
+/// is not tracked by the destructors of CallFrame alone: it is reset by every top-level call, which is any call made
+/// while Rust is not calling into JavaScript, and restored by `to_js_unwind` when an exception unwinds a JsCall.
+#[derive(Default)]
+struct Stash {
+    values: Vec<(u32, Box<dyn Any>)>,
+    bytes: Vec<(u32, Vec<u8>)>,
+    /// Number of exported calls currently on the stack (see CallFrame)
+    call_depth: u32,
+    /// The call depth at the start of each call from Rust into JavaScript that is in progress (see JsCall)
+    js_calls: Vec<u32>,
+    /// Approximate number of bytes currently stashed (see heap_size), and the peak usage
+    size: usize,
+    peak_len: usize,
+    peak_size: usize,
+    /// Number of nested JavaScript-side scopes (see `rs.scope`) that are currently open.
+    /// While any scope is open, clearing is deferred until the outermost scope is exited.
+    scope_depth: u32,
+}
+
+impl Stash {
+    /// The generation of the innermost call, or 0 outside of any call
+    fn generation(&self) -> u32 {
+        self.call_depth.saturating_sub(1)
+    }
+
+    fn push<T: 'static>(&mut self, x: T) {
+        self.size += heap_size(&x);
+        self.peak_size = self.peak_size.max(self.size);
+        self.peak_len = self.peak_len.max(self.values.len() + self.bytes.len() + 1);
+        let gen = self.generation();
+        // Move byte buffers out of an Option (which does not move their heap data) rather than boxing them
+        let mut x = Some(x);
+        let any = &mut x as &mut dyn Any;
+        if let Some(bytes) = any.downcast_mut::<Option<Vec<u8>>>() {
+            self.bytes.extend(bytes.take().map(|x| (gen, x)));
+        } else if let Some(string) = any.downcast_mut::<Option<String>>() {
+            self.bytes
+                .extend(string.take().map(|x| (gen, x.into_bytes())));
+        } else {
+            self.values
+                .extend(x.map(|x| (gen, Box::new(x) as Box<dyn Any>)));
+        }
+    }
+
+    /// The number of values stashed by the innermost call
+    fn len(&self) -> usize {
+        let gen = self.generation();
+        let values = self.values.iter().filter(|(g, _)| *g == gen).count();
+        let bytes = self.bytes.iter().filter(|(g, _)| *g == gen).count();
+        values + bytes
+    }
+
+    fn clear(&mut self) {
+        if self.scope_depth > 0 {
+            return;
+        }
+        let gen = self.generation();
+        if gen == 0 {
+            self.values.clear();
+            self.bytes.clear();
+            self.size = 0;
+        } else {
+            self.values.retain(|(g, _)| *g < gen);
+            self.bytes.retain(|(g, _)| *g < gen);
+            let values = self.values.iter().map(|(_, x)| heap_size(&**x));
+            let bytes = self.bytes.iter().map(|(_, x)| heap_size(x));
+            self.size = values.chain(bytes).sum();
+        }
+    }
+}
+
+/// Approximates the memory used by a stashed value: its own size, plus the heap capacity of the
+/// commonly stashed types (strings and vectors of numbers). Other heap data is not counted.
+fn heap_size(x: &dyn Any) -> usize {
+    macro_rules! vec_capacity {
+        ($( $type:ty $(,)? )*) => {
+            $(
+                if let Some(x) = x.downcast_ref::<Vec<$type>>() {
+                    return std::mem::size_of_val(x) + x.capacity() * std::mem::size_of::<$type>();
+                }
+            )*
+        };
+    }
+    vec_capacity!(u8, i8, u16, i16, u32, i32, f32, u64, i64, f64, usize, isize);
+    if let Some(x) = x.downcast_ref::<String>() {
+        return std::mem::size_of_val(x) + x.capacity();
+    }
+    std::mem::size_of_val(x)
+}
+
+/// Statistics about the values currently kept alive, for diagnosing leaks (see stats.rs)
+pub(crate) struct KeepAliveStats {
+    pub(crate) len: usize,
+    pub(crate) size: usize,
+    pub(crate) peak_len: usize,
+    pub(crate) peak_size: usize,
+}
+
+pub(crate) fn keepalive_stats() -> KeepAliveStats {
+    KEEPALIVE.with_borrow(|stash| KeepAliveStats {
+        len: stash.values.len() + stash.bytes.len(),
+        size: stash.size,
+        peak_len: stash.peak_len,
+        peak_size: stash.peak_size,
+    })
+}
+
+thread_local! {
+    static KEEPALIVE: RefCell<Stash> = RefCell::default();
+}
 
 pub struct KeepAlive<T>(Wasm, PhantomData<T>);
 
@@ -17,18 +135,125 @@ pub struct KeepAlive<T>(Wasm, PhantomData<T>);
 // is a semantically valid operation while KEEPALIVE owns the value.
 impl<T> KeepAlive<T>
 where
-    T: Send + Sync + 'static,
+    T: 'static,
     for<'a> &'a T: IntoWasm,
 {
     pub fn new(x: T) -> KeepAlive<T> {
         let wasm = (&x).into_wasm();
-        KEEPALIVE.write().unwrap().push(Box::new(x));
-        return KeepAlive(wasm, PhantomData);
+        keep_alive(x);
+        KeepAlive(wasm, PhantomData)
     }
 }
 
+/// Stashes a value without encoding it, to keep memory that is referred to by another stashed value alive
+pub(crate) fn keep_alive<T: 'static>(x: T) {
+    KEEPALIVE.with_borrow_mut(|stash| stash.push(x));
+}
+
 pub fn clear_keepalive() {
-    KEEPALIVE.write().unwrap().clear();
+    KEEPALIVE.with_borrow_mut(Stash::clear);
+}
+
+/// Marks the duration of an exported call, for tracking the generation of stashed values.
+/// Entered at the start of every exported call, before clearing the stash.
+#[doc(hidden)]
+pub struct CallFrame(());
+
+impl CallFrame {
+    pub fn enter() -> CallFrame {
+        KEEPALIVE.with_borrow_mut(|stash| {
+            // Only calls made from JavaScript while Rust is calling into it are nested, so any other call is a
+            // top-level call, whose depth is reset in case a trap skipped the destructors of earlier frames
+            if stash.js_calls.is_empty() {
+                stash.call_depth = 0;
+            }
+            stash.call_depth += 1;
+        });
+        CallFrame(())
+    }
+}
+
+impl Drop for CallFrame {
+    fn drop(&mut self) {
+        KEEPALIVE.with_borrow_mut(|stash| stash.call_depth -= 1);
+    }
+}
+
+/// Marks the duration of a call from Rust into JavaScript (through an imported function or a JsFn), during which
+/// JavaScript may call back into exported functions, which are then nested within the current call.
+#[doc(hidden)]
+pub struct JsCall(());
+
+impl JsCall {
+    pub fn enter() -> JsCall {
+        KEEPALIVE.with_borrow_mut(|stash| stash.js_calls.push(stash.call_depth));
+        JsCall(())
+    }
+}
+
+impl Drop for JsCall {
+    fn drop(&mut self) {
+        KEEPALIVE.with_borrow_mut(|stash| stash.js_calls.pop());
+    }
+}
+
+// Export used by the JavaScript side when an exception propagates out of a call from Rust into JavaScript, which
+// unwinds the innermost JsCall (and any exported calls nested within it) without running their destructors.
+#[unsafe(export_name = "to_js_unwind")]
+extern "C" fn unwind() {
+    KEEPALIVE.with_borrow_mut(|stash| {
+        if let Some(call_depth) = stash.js_calls.pop() {
+            stash.call_depth = call_depth;
+        }
+    });
+}
+
+/// Used by functions exported with #[js(preserve_keepalive)], which do not clear the stash,
+/// to check in debug builds that they do not add to it either.
+#[doc(hidden)]
+pub struct PreserveKeepAlive {
+    name: &'static str,
+    len: usize,
+}
+
+impl PreserveKeepAlive {
+    pub fn new(name: &'static str) -> Self {
+        let len = if cfg!(debug_assertions) {
+            KEEPALIVE.with_borrow(Stash::len)
+        } else {
+            0
+        };
+        PreserveKeepAlive { name, len }
+    }
+}
+
+impl Drop for PreserveKeepAlive {
+    fn drop(&mut self) {
+        if cfg!(debug_assertions) && !std::thread::panicking() {
+            let len = KEEPALIVE.with_borrow(Stash::len);
+            assert_eq!(
+                len, self.len,
+                "`{}` is exported with preserve_keepalive, but stashed a value with KeepAlive",
+                self.name
+            );
+        }
+    }
+}
+
+// Exports used by the JavaScript side to open and close a scope
+
+#[unsafe(export_name = "to_js_scope_enter")]
+extern "C" fn scope_enter() {
+    KEEPALIVE.with_borrow_mut(|stash| stash.scope_depth += 1);
+}
+
+#[unsafe(export_name = "to_js_scope_exit")]
+extern "C" fn scope_exit() {
+    KEEPALIVE.with_borrow_mut(|stash| {
+        debug_assert!(stash.scope_depth > 0, "scope exited without being entered");
+        stash.scope_depth -= 1;
+        stash.clear();
+    });
 }
 
 // ToWasm impl
@@ -57,7 +282,135 @@ impl<T> TypeInfo for KeepAlive<T>
 where
     for<'a> &'a T: TypeInfo,
 {
-    fn type_info() -> Info {
-        <&T>::type_info()
+    const INFO: Info = <&T>::INFO;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn len() -> usize {
+        keepalive_stats().len
+    }
+
+    crate::to_js! {
+        @preserve_keepalive
+        fn keepalive_test_getter() -> u32 {
+            1
+        }
+    }
+
+    crate::to_js! {
+        fn keepalive_test_clearing() -> u32 {
+            2
+        }
+    }
+
+    // Call the exports defined above through their exported symbols, as JavaScript would
+    mod exports {
+        extern "C" {
+            pub fn keepalive_test_getter() -> f64;
+            pub fn keepalive_test_clearing() -> f64;
+        }
+    }
+
+    fn call_depth() -> u32 {
+        KEEPALIVE.with_borrow(|stash| stash.call_depth)
+    }
+
+    // Stash a value from within an exported call, as a nested call would
+    fn nested_call(x: u32) {
+        let _call = JsCall::enter();
+        let _frame = CallFrame::enter();
+        clear_keepalive();
+        keep_alive(x);
+    }
+
+    #[test]
+    fn nested_calls_only_clear_their_own_generation() {
+        let outer = CallFrame::enter();
+        clear_keepalive();
+        keep_alive(vec![1u8, 2, 3]);
+        nested_call(1);
+        assert_eq!(len(), 2);
+        // The second nested call clears the value stashed by the first, but not that of the outer call
+        nested_call(2);
+        assert_eq!(len(), 2);
+        drop(outer);
+        assert_eq!(call_depth(), 0);
+
+        // The next top-level call clears everything
+        let _frame = CallFrame::enter();
+        clear_keepalive();
+        assert_eq!(len(), 0);
+    }
+
+    #[test]
+    fn top_level_calls_reset_the_call_depth() {
+        // A trap skips the destructor of the frame on the stack
+        std::mem::forget(CallFrame::enter());
+        assert_eq!(call_depth(), 1);
+        let frame = CallFrame::enter();
+        assert_eq!(call_depth(), 1);
+        drop(frame);
+        assert_eq!(call_depth(), 0);
+    }
+
+    #[test]
+    fn unwinding_restores_the_call_depth() {
+        let outer = CallFrame::enter();
+        clear_keepalive();
+        keep_alive(vec![1u8, 2, 3]);
+        // An exception unwinds a call into JavaScript and the exported call nested within it
+        std::mem::forget(JsCall::enter());
+        std::mem::forget(CallFrame::enter());
+        keep_alive(1u32);
+        unwind();
+        assert_eq!(call_depth(), 1);
+        nested_call(2);
+        assert_eq!(len(), 2);
+        drop(outer);
+    }
+
+    #[test]
+    fn preserve_keepalive_exports_do_not_clear_the_stash() {
+        keep_alive(vec![1u8, 2, 3]);
+        assert_eq!(unsafe { exports::keepalive_test_getter() }, 1.0);
+        assert_eq!(len(), 1);
+        assert_eq!(unsafe { exports::keepalive_test_clearing() }, 2.0);
+        assert_eq!(len(), 0);
+    }
+
+    #[test]
+    fn preserve_keepalive_allows_calls_that_do_not_stash() {
+        keep_alive(vec![1u8, 2, 3]);
+        let preserve = PreserveKeepAlive::new("getter");
+        drop(preserve);
+        assert_eq!(len(), 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(
+        expected = "`stasher` is exported with preserve_keepalive, but stashed a value with KeepAlive"
+    )]
+    fn preserve_keepalive_panics_if_a_value_is_stashed() {
+        let preserve = PreserveKeepAlive::new("stasher");
+        let _ = KeepAlive::new(String::from("stashed"));
+        drop(preserve);
+    }
+
+    #[test]
+    fn scopes_defer_clearing_until_the_outermost_one_exits() {
+        scope_enter();
+        keep_alive(vec![1u8, 2, 3]);
+        clear_keepalive();
+        assert_eq!(len(), 1);
+        scope_enter();
+        keep_alive(String::from("nested"));
+        scope_exit();
+        assert_eq!(len(), 2);
+        scope_exit();
+        assert_eq!(len(), 0);
     }
 }
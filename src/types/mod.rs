@@ -1,12 +1,20 @@
 mod bool;
 mod errorstring;
+pub(crate) mod iter;
+pub(crate) mod jsfn;
 pub(crate) mod json;
 pub(crate) mod keepalive;
+pub(crate) mod msgpack;
 pub(crate) mod number;
+pub(crate) mod owned;
 pub(crate) mod packed;
+pub(crate) mod pinned;
 mod pointer;
+pub(crate) mod promise;
 mod string;
+pub(crate) mod structured;
 mod typedarray;
 mod vec;
+pub(crate) mod viewref;
 mod void;
 mod wrappers;
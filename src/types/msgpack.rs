@@ -0,0 +1,40 @@
+#![cfg(feature = "msgpack")]
+
+use crate::niche::{HasNiche, Niche};
+use crate::typeinfo::{ArrayType, Transform};
+use crate::KeepAlive;
+use crate::{ToWasm, Wasm};
+
+// Represents a value to be serialized to MessagePack using serde.
+// Unlike JSON, this preserves 64-bit integers (decoded as BigInt), byte strings
+// (decoded as Uint8Array, eg. via serde_bytes) and non-finite floats.
+pub struct MsgPack(KeepAlive<Vec<u8>>);
+
+impl MsgPack {
+    pub fn new(x: &impl serde::Serialize) -> MsgPack {
+        let v = rmp_serde::to_vec_named(x).expect("MessagePack serialization failed");
+        MsgPack(KeepAlive::new(v))
+    }
+}
+
+// ToWasm impl
+//
+
+impl ToWasm for MsgPack {
+    fn to_wasm(&self) -> Wasm {
+        self.0.to_wasm()
+    }
+}
+
+// HasNiche impl
+
+impl HasNiche for MsgPack {
+    const N: Niche = <&Vec<u8>>::N;
+}
+
+// TypeInfo impl
+//
+
+impl_typeinfo! {
+    [MsgPack, ArrayType::U8, true, Transform::MsgPack],
+}
@@ -1,6 +1,6 @@
 use crate::niche::{HasNiche, Niche};
 use crate::typeinfo::{ArrayType, Transform};
-use crate::{ToWasm, Wasm};
+use crate::{FromWasm, ToWasm, Wasm};
 
 // todo: should this impl ToWasm? Can we get rid of to_f64?
 pub(crate) trait Number: 'static + Send + Sync + Copy {}
@@ -19,6 +19,12 @@ macro_rules! impl_number {
             impl HasNiche for $type {
                 const N: Niche = Niche::HighBitsNaN;
             }
+
+            impl FromWasm for $type {
+                fn from_wasm(value: f64) -> Self {
+                    value as $type
+                }
+            }
         )*
     };
 }
@@ -44,6 +50,21 @@ impl ToWasm for i64 {
     }
 }
 
+// FromWasm impl
+// (JavaScript passes 64-bit integers as numbers, so they are converted rather than reinterpreted)
+
+impl FromWasm for u64 {
+    fn from_wasm(value: f64) -> Self {
+        value as u64
+    }
+}
+
+impl FromWasm for i64 {
+    fn from_wasm(value: f64) -> Self {
+        value as i64
+    }
+}
+
 // HasNiche impl
 // (There's no blanket implementation for Number since since not *all* numbers
 // have niches; in particular, u64 and i64 have no niches available.)
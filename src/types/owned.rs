@@ -0,0 +1,182 @@
+use crate::types::number::Number;
+use std::alloc::Layout;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+// Represents a value allocated and filled in by JavaScript (with `rs.allocArray`), whose ownership is passed to Rust
+// as the argument of an exported function. It is received as an f64-encoded (ptr, len) pair, like the U32Pair
+// representation of a slice, and converted into the Rust value with `into_inner`. Dropping it frees the memory.
+// Since JavaScript can pass any number, the pair is only accepted if it describes an allocation made by
+// `to_js_alloc_bytes` that has not been freed or passed to Rust before, whose layout is that of the Rust value.
+// Note: The layout (rather than the typed array type) is what is checked, so eg. a Float32Array can be received as
+// an Owned<Vec<u32>>, which reinterprets its elements.
+#[repr(transparent)]
+pub struct Owned<T: FromOwned>(f64, PhantomData<T>);
+
+/// Types that can take ownership of memory allocated by JavaScript
+pub trait FromOwned {
+    /// The layout of the allocation for a value of `len` elements, or None if there is no such value
+    fn layout(len: u32) -> Option<Layout>;
+
+    /// # Safety
+    /// The (ptr, len) pair must describe memory allocated by `to_js_alloc_bytes` with the layout returned by
+    /// `layout(len)`, which has not been freed or passed to Rust before.
+    unsafe fn from_owned(ptr: u32, len: u32) -> Self;
+}
+
+impl<T: Number> FromOwned for Vec<T> {
+    fn layout(len: u32) -> Option<Layout> {
+        Layout::array::<T>(len as usize).ok()
+    }
+
+    unsafe fn from_owned(ptr: u32, len: u32) -> Self {
+        let len = len as usize;
+        Vec::from_raw_parts(ptr as usize as *mut T, len, len)
+    }
+}
+
+impl<T: FromOwned> Owned<T> {
+    /// Takes ownership of the value.
+    /// Panics if JavaScript passed anything other than an array allocated with `allocArray` with the layout of T.
+    pub fn into_inner(self) -> T {
+        let owned = take::<T>(self.0);
+        std::mem::forget(self);
+        let (ptr, len) = owned.expect(
+            "Owned: expected an array allocated with allocArray with the layout of the value",
+        );
+        unsafe { T::from_owned(ptr, len) }
+    }
+}
+
+impl<T: FromOwned> Drop for Owned<T> {
+    fn drop(&mut self) {
+        // Values that were not allocated by JavaScript are not freed
+        if let Some((ptr, len)) = take::<T>(self.0) {
+            drop(unsafe { T::from_owned(ptr, len) });
+        }
+    }
+}
+
+thread_local! {
+    // The layout of each allocation made by `to_js_alloc_bytes` that has not yet been freed or passed to Rust,
+    // by address. Zero-size allocations, which are not real allocations, are not included.
+    static ALLOCATIONS: RefCell<HashMap<usize, Layout>> = RefCell::default();
+}
+
+// Remove the allocation described by an f64-encoded (ptr, len) pair from the table, if it is one with the layout
+// of a T of len elements, returning the pair
+fn take<T: FromOwned>(value: f64) -> Option<(u32, u32)> {
+    let bits = value.to_bits();
+    let (ptr, len) = (bits as u32, (bits >> 32) as u32);
+    take_allocation(ptr as usize, T::layout(len)?).then_some((ptr, len))
+}
+
+fn take_allocation(ptr: usize, layout: Layout) -> bool {
+    if layout.size() == 0 {
+        // Zero-size allocations are represented by a dangling pointer (see alloc_bytes)
+        return ptr == layout.align();
+    }
+    ALLOCATIONS.with_borrow_mut(|allocations| {
+        let found = allocations.get(&ptr) == Some(&layout);
+        if found {
+            allocations.remove(&ptr);
+        }
+        found
+    })
+}
+
+// Exports used by the JavaScript side to allocate and free memory for Owned values.
+// Zero-size allocations are not allowed by the allocator, so these return and accept a dangling pointer instead.
+// Since JavaScript can ask for any size, a size that is not a valid layout (eg. one over isize::MAX) returns a null
+// pointer rather than trapping, which `allocArray` turns into a RangeError.
+
+#[unsafe(export_name = "to_js_alloc_bytes")]
+extern "C" fn alloc_bytes(len: usize, align: usize) -> *mut u8 {
+    let Ok(layout) = Layout::from_size_align(len, align) else {
+        return std::ptr::null_mut();
+    };
+    if len == 0 {
+        return align as *mut u8;
+    }
+    let ptr = unsafe { std::alloc::alloc(layout) };
+    if ptr.is_null() {
+        std::alloc::handle_alloc_error(layout);
+    }
+    ALLOCATIONS.with_borrow_mut(|allocations| allocations.insert(ptr as usize, layout));
+    ptr
+}
+
+// Memory that was not allocated by `to_js_alloc_bytes` with the same layout, or that has already been freed or passed
+// to Rust, is not freed.
+#[unsafe(export_name = "to_js_free_bytes")]
+extern "C" fn free_bytes(ptr: *mut u8, len: usize, align: usize) {
+    let Ok(layout) = Layout::from_size_align(len, align) else {
+        return;
+    };
+    if len > 0 && take_allocation(ptr as usize, layout) {
+        unsafe { std::alloc::dealloc(ptr, layout) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_allocated(ptr: *mut u8) -> bool {
+        ALLOCATIONS.with_borrow(|allocations| allocations.contains_key(&(ptr as usize)))
+    }
+
+    #[test]
+    fn allocations_are_only_taken_with_their_layout() {
+        let f64s = |len| <Vec<f64>>::layout(len).unwrap();
+        let ptr = alloc_bytes(64, 8);
+        let addr = ptr as usize;
+        // A number that is not an allocation, and the allocation with the wrong element size or length
+        assert!(!take_allocation(5, f64s(8)));
+        assert!(!take_allocation(addr, <Vec<u8>>::layout(64).unwrap()));
+        assert!(!take_allocation(addr, f64s(4)));
+        assert!(is_allocated(ptr));
+
+        assert!(take_allocation(addr, f64s(8)));
+        assert!(!is_allocated(ptr));
+        // An allocation can only be taken once
+        assert!(!take_allocation(addr, f64s(8)));
+        drop(unsafe { Vec::from_raw_parts(ptr as *mut f64, 8, 8) });
+    }
+
+    #[test]
+    fn zero_size_allocations_are_dangling() {
+        let ptr = alloc_bytes(0, 4);
+        assert_eq!(ptr as usize, 4);
+        assert!(take_allocation(4, <Vec<u32>>::layout(0).unwrap()));
+        assert!(!take_allocation(4, <Vec<f64>>::layout(0).unwrap()));
+        free_bytes(ptr, 0, 4);
+    }
+
+    #[test]
+    fn invalid_layouts_are_not_allocated() {
+        assert!(alloc_bytes(usize::MAX - 2, 4).is_null());
+        assert!(alloc_bytes(isize::MAX as usize + 1, 1).is_null());
+        assert!(alloc_bytes(16, 3).is_null());
+        assert!(alloc_bytes(0, 3).is_null());
+    }
+
+    #[test]
+    fn only_allocations_are_freed() {
+        let ptr = alloc_bytes(16, 4);
+        free_bytes(5 as *mut u8, 16, 4);
+        free_bytes(ptr, 16, 8);
+        assert!(is_allocated(ptr));
+        free_bytes(ptr, 16, 4);
+        assert!(!is_allocated(ptr));
+        // Freeing again does nothing
+        free_bytes(ptr, 16, 4);
+    }
+
+    #[test]
+    fn owned_values_that_are_not_allocations_are_not_freed() {
+        // The (ptr, len) pair of a number passed by JavaScript
+        drop(Owned::<Vec<f64>>(5.0, PhantomData));
+    }
+}
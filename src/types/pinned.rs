@@ -0,0 +1,102 @@
+use crate::niche::{HasNiche, Niche};
+use crate::typeinfo::{Info, TypeInfo};
+use crate::{alloc, dealloc, IntoWasm, ToWasm, Wasm};
+use std::marker::PhantomData;
+
+// Represents an owned value that is kept alive until JavaScript explicitly releases it, rather than until the next
+// call (as with KeepAlive). The value is returned to JavaScript as an object of the form { value, release }.
+// Note: The value is moved into a heap-allocated handle, which is what is returned across the FFI boundary;
+// JavaScript then retrieves the value itself from the handle using `to_js_pinned_value`.
+pub struct Pinned<T>(Wasm, PhantomData<T>);
+
+// Type-erased value stored behind a handle
+trait PinnedValue {
+    fn value(&self) -> Wasm;
+}
+
+impl<T> PinnedValue for T
+where
+    for<'a> &'a T: IntoWasm,
+{
+    fn value(&self) -> Wasm {
+        self.into_wasm()
+    }
+}
+
+type Handle = *mut Box<dyn PinnedValue>;
+
+impl<T> Pinned<T>
+where
+    T: 'static,
+    for<'a> &'a T: IntoWasm,
+{
+    pub fn new(x: T) -> Pinned<T> {
+        let value: Box<dyn PinnedValue> = Box::new(x);
+        Pinned(alloc(value).to_wasm(), PhantomData)
+    }
+}
+
+// Exports used by the JavaScript side to retrieve the value behind a handle, and to release it
+
+#[unsafe(export_name = "to_js_pinned_value")]
+extern "C" fn pinned_value(handle: Handle) -> f64 {
+    let value = unsafe { &*handle };
+    value.value().value()
+}
+
+#[unsafe(export_name = "to_js_release")]
+extern "C" fn release(handle: Handle) {
+    dealloc(handle);
+}
+
+// ToWasm impl
+//
+
+impl<T> ToWasm for Pinned<T> {
+    fn to_wasm(&self) -> Wasm {
+        self.0.clone()
+    }
+}
+
+// HasNiche impl
+// (the niche is that of the handle, rather than of the value)
+
+impl<T> HasNiche for Pinned<T> {
+    const N: Niche = Niche::HighBitsNaN;
+}
+
+// TypeInfo impl
+//
+
+impl<T> TypeInfo for Pinned<T>
+where
+    for<'a> &'a T: TypeInfo,
+{
+    const INFO: Info = <&T>::INFO.pinned();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn live_handles() -> usize {
+        let name = std::any::type_name::<Box<dyn PinnedValue>>();
+        let allocs = crate::stats().allocs;
+        allocs
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map_or(0, |(_, count)| *count)
+    }
+
+    #[test]
+    fn handles_are_live_until_released() {
+        let before = live_handles();
+        let value: Box<dyn PinnedValue> = Box::new(String::from("pinned"));
+        let handle = alloc(value);
+        assert_eq!(live_handles(), before + 1);
+        // The value is a (ptr, len) pair, of which only the length is meaningful on non-wasm32 targets
+        assert_eq!(pinned_value(handle).to_bits() >> 32, 6);
+        release(handle);
+        assert_eq!(live_handles(), before);
+    }
+}
@@ -32,13 +32,9 @@ impl<T> HasNiche for *mut T {
 //
 
 impl<T> TypeInfo for *const T {
-    fn type_info() -> Info {
-        Info::new(ArrayType::None, false, Transform::Identity)
-    }
+    const INFO: Info = Info::new(ArrayType::None, false, Transform::Identity);
 }
 
 impl<T> TypeInfo for *mut T {
-    fn type_info() -> Info {
-        Info::new(ArrayType::None, false, Transform::Identity)
-    }
+    const INFO: Info = Info::new(ArrayType::None, false, Transform::Identity);
 }
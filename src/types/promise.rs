@@ -0,0 +1,34 @@
+use crate::executor::spawn;
+use crate::typeinfo::{Info, TypeInfo};
+use crate::{IntoWasm, ToWasm, Wasm};
+use std::future::Future;
+use std::marker::PhantomData;
+
+// Represents a future that is returned to JavaScript as a Promise, which resolves to the future's output
+// (or rejects if the output is an Err). This is the return type of functions exported with #[js] async fn.
+pub struct JsPromise<T>(Wasm, PhantomData<T>);
+
+impl<T: IntoWasm + 'static> JsPromise<T> {
+    pub fn spawn(future: impl Future<Output = T> + 'static) -> JsPromise<T> {
+        JsPromise(spawn(future).to_wasm(), PhantomData)
+    }
+}
+
+// ToWasm impl
+//
+
+impl<T> ToWasm for JsPromise<T> {
+    fn to_wasm(&self) -> Wasm {
+        self.0.clone()
+    }
+}
+
+// HasNiche impl
+// (none since the type info for a promise is that of its output, which may itself be an Option or Result)
+
+// TypeInfo impl
+//
+
+impl<T: TypeInfo> TypeInfo for JsPromise<T> {
+    const INFO: Info = T::INFO.promise();
+}
@@ -0,0 +1,77 @@
+#![cfg(feature = "structured")]
+
+use crate::niche::{HasNiche, Niche};
+use crate::ser::{Serializer, VIEW_NAME};
+use crate::typeinfo::{ArrayType, Transform};
+use crate::types::keepalive::keep_alive;
+use crate::types::packed::U32Pair;
+use crate::{ToWasm, Wasm};
+use serde::Serialize;
+
+// Represents a value to be serialized using serde into JavaScript objects and arrays,
+// where sequences of numbers (eg. Vec<f64> fields) are returned as typed array views
+// into WebAssembly memory rather than as arrays of numbers.
+pub struct Structured(Wasm);
+
+impl Structured {
+    /// Serializes the value, which is kept alive along with its encoding so that slices serialized with
+    /// `as_view` can be returned as views of the value's own memory.
+    pub fn new<T: Serialize + 'static>(x: T) -> Structured {
+        // Box the value so that any slices stored inline (eg. arrays) do not move when it is stashed
+        let x = Box::new(x);
+        let (words, len) = Serializer::encode(&*x).expect("serialization failed");
+        // The encoding is returned as a byte slice even though it is stored
+        // as u64 words in order to keep the data section 8-byte aligned.
+        let wasm = U32Pair([words.as_ptr() as u32, len as u32]).to_wasm();
+        keep_alive(words);
+        keep_alive(x);
+        Structured(wasm)
+    }
+}
+
+/// Serializes a slice of numbers so that `Structured` returns it as a typed array view of the slice itself,
+/// without copying it. For use with `#[serde(serialize_with = "to_js::as_view")]` on fields of a value passed
+/// to `Structured::new`, which keeps the value alive. Other serializers serialize the slice as usual.
+pub fn as_view<S: serde::Serializer, T: Serialize>(
+    x: &[T],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    struct Slice<'a, T>(&'a [T]);
+
+    impl<T: Serialize> Serialize for Slice<'_, T> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::SerializeSeq;
+            // Pass each element itself by reference (rather than a reference to it, as collect_seq would),
+            // so that its address is that of the element in the slice
+            let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+            for x in self.0 {
+                seq.serialize_element(x)?;
+            }
+            seq.end()
+        }
+    }
+
+    serializer.serialize_newtype_struct(VIEW_NAME, &Slice(x))
+}
+
+// ToWasm impl
+//
+
+impl ToWasm for Structured {
+    fn to_wasm(&self) -> Wasm {
+        self.0.clone()
+    }
+}
+
+// HasNiche impl
+
+impl HasNiche for Structured {
+    const N: Niche = Niche::LowBitsOne;
+}
+
+// TypeInfo impl
+//
+
+impl_typeinfo! {
+    [Structured, ArrayType::U8, true, Transform::Structured],
+}
@@ -54,25 +54,17 @@ impl<T: Number> HasNiche for &mut Box<[T]> {
 //
 
 impl<T: Number + TypeInfo> TypeInfo for &[T] {
-    fn type_info() -> Info {
-        T::type_info().array().identity_transform()
-    }
+    const INFO: Info = T::INFO.array().identity_transform();
 }
 
 impl<T: Number + TypeInfo> TypeInfo for &Box<[T]> {
-    fn type_info() -> Info {
-        T::type_info().array().identity_transform()
-    }
+    const INFO: Info = T::INFO.array().identity_transform();
 }
 
 impl<T: Number + TypeInfo> TypeInfo for &mut [T] {
-    fn type_info() -> Info {
-        T::type_info().array().identity_transform()
-    }
+    const INFO: Info = T::INFO.array().identity_transform();
 }
 
 impl<T: Number + TypeInfo> TypeInfo for &mut Box<[T]> {
-    fn type_info() -> Info {
-        T::type_info().array().identity_transform()
-    }
+    const INFO: Info = T::INFO.array().identity_transform();
 }
@@ -26,7 +26,5 @@ impl<T: Number> HasNiche for &Vec<T> {
 //
 
 impl<T: TypeInfo + Number> TypeInfo for &Vec<T> {
-    fn type_info() -> Info {
-        <&[T]>::type_info()
-    }
+    const INFO: Info = <&[T]>::INFO;
 }
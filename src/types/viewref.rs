@@ -0,0 +1,81 @@
+use crate::niche::{HasNiche, Niche};
+use crate::typeinfo::{Info, TypeInfo};
+use crate::types::number::Number;
+use crate::{ToWasm, Wasm};
+
+// Wraps a borrowed typed array return value (eg. a slice of a field of a pinned value) so that it is returned to
+// JavaScript as a ViewRef rather than as a typed array. Growing WebAssembly memory detaches every existing view of it,
+// so a ViewRef stores the array's location instead, and re-derives the view on access if memory has grown.
+// Note: This does not extend the lifetime of the data, which must remain valid for as long as the ViewRef is used.
+// For that reason only borrowed arrays can be wrapped (see ViewSource).
+pub struct ViewRef<T: ViewSource>(pub T);
+
+/// Typed arrays that a `ViewRef` can wrap: slices, boxed slices, and vectors, borrowed from data that outlives the call
+/// that returns them. Values that are freed by a later call, like a `KeepAlive`, cannot be wrapped:
+///
+/// ```compile_fail
+/// use to_js::{KeepAlive, ViewRef};
+///
+/// fn samples() -> ViewRef<KeepAlive<Vec<f32>>> {
+///     ViewRef(KeepAlive::new(vec![1.0]))
+/// }
+/// ```
+pub trait ViewSource: private::Sealed {}
+
+impl<T: Number> ViewSource for &[T] {}
+impl<T: Number> ViewSource for &Box<[T]> {}
+impl<T: Number> ViewSource for &Vec<T> {}
+
+mod private {
+    use crate::types::number::Number;
+
+    pub trait Sealed {}
+
+    impl<T: Number> Sealed for &[T] {}
+    impl<T: Number> Sealed for &Box<[T]> {}
+    impl<T: Number> Sealed for &Vec<T> {}
+}
+
+// ToWasm impl
+//
+
+impl<T: ViewSource + ToWasm> ToWasm for ViewRef<T> {
+    fn to_wasm(&self) -> Wasm {
+        self.0.to_wasm()
+    }
+}
+
+// HasNiche impl
+//
+
+impl<T: ViewSource + HasNiche> HasNiche for ViewRef<T> {
+    const N: Niche = T::N;
+}
+
+// TypeInfo impl
+//
+
+impl<T: ViewSource + TypeInfo> TypeInfo for ViewRef<T> {
+    const INFO: Info = T::INFO.view_ref();
+}
+
+// Lets the JavaScript side know whether to check for typed arrays detached by memory growth,
+// which it only does in debug builds since doing so requires tracking every returned view.
+#[unsafe(export_name = "to_js_debug_assertions")]
+extern "C" fn debug_assertions() -> u32 {
+    cfg!(debug_assertions) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_ref_info() {
+        // [is_result, is_option, is_array, array_type (F32), transform (ViewRef), is_promise, is_pinned, converts_args]
+        let info = <ViewRef<&Vec<f32>> as TypeInfo>::INFO;
+        assert_eq!(info.to_bytes(), [0, 0, 1, 6, 17, 0, 0, 0]);
+        let info = <Option<ViewRef<&[u8]>> as TypeInfo>::INFO;
+        assert_eq!(info.to_bytes(), [0, 1, 1, 0, 17, 0, 0, 0]);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::niche::{HasNiche, Niche};
 use crate::typeinfo::{ArrayType, Transform};
-use crate::{ToWasm, Wasm};
+use crate::{FromWasm, ToWasm, Wasm};
 
 // ToWasm impl
 //
@@ -11,6 +11,13 @@ impl ToWasm for () {
     }
 }
 
+// FromWasm impl
+//
+
+impl FromWasm for () {
+    fn from_wasm(_value: f64) -> Self {}
+}
+
 // HasNiche impl
 //
 
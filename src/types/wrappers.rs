@@ -57,13 +57,28 @@ impl<T: HasNiche + ToWasm, E: ErrorString> ToWasm for Option<Result<T, E>> {
 //
 
 impl<T: TypeInfo> TypeInfo for Option<T> {
-    fn type_info() -> Info {
-        T::type_info().option()
-    }
+    const INFO: Info = T::INFO.option();
 }
 
 impl<T: TypeInfo, E> TypeInfo for Result<T, E> {
-    fn type_info() -> Info {
-        T::type_info().result()
+    const INFO: Info = T::INFO.result();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(value: impl ToWasm) -> u64 {
+        value.to_wasm().value().to_bits()
+    }
+
+    #[test]
+    fn ok_none_is_encoded_as_none() {
+        // Ok(None) must be distinguishable from Err, whose error pointer (in the low bits) is nonzero
+        let ok_none: Result<Option<u32>, String> = Ok(None);
+        assert_eq!(bits(ok_none), bits(None::<u32>));
+        assert_eq!(bits(None::<u32>), 0xfff80000_00000000);
+        let ok_some: Result<Option<u32>, String> = Ok(Some(5));
+        assert_eq!(ok_some.to_wasm().value(), 5.0);
     }
 }
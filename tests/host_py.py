@@ -0,0 +1,155 @@
+# Tests of the Python decoder (src/host.py) against a mock instance, run by tests/python.rs. If TO_JS_PYTHON is
+# set, it is the path of a module generated by the `to_js` binary for the module described there, which is
+# checked as well.
+
+import importlib.util
+import os
+import struct
+import unittest
+from pathlib import Path
+
+
+def load(path, name):
+    spec = importlib.util.spec_from_file_location(name, path)
+    module = importlib.util.module_from_spec(spec)
+    spec.loader.exec_module(module)
+    return module
+
+
+host = load(Path(__file__).parent.parent / "src" / "host.py", "host")
+
+
+def f64(bits):
+    return struct.unpack("<d", struct.pack("<Q", bits))[0]
+
+
+def pair(low, high):
+    return f64(high << 32 | low)
+
+
+# Type info, as the 8 bytes of an Info:
+# (is_result, is_option, is_array, array_type, transform, is_promise, is_pinned, converts_args)
+def info(transform, array_type=10, is_array=0, is_result=0, is_option=0, is_pinned=0):
+    return bytes([is_result, is_option, is_array, array_type, transform, 0, is_pinned, 0])
+
+
+class MockInstance:
+    """An instance whose exports are Python functions, with 64 KiB of memory."""
+
+    def __init__(self, exports, abi_version=host.ABI_VERSION):
+        self.memory = bytearray(65536)
+        self.exports = dict(exports)
+        if abi_version is not None:
+            self.exports["to_js_abi_version"] = lambda: abi_version
+
+    def call(self, name, *args):
+        return self.exports[name](*args)
+
+    def read(self, ptr, length):
+        return bytes(self.memory[ptr : ptr + length])
+
+    def write(self, ptr, data):
+        self.memory[ptr : ptr + len(data)] = data
+
+
+class DecodeTest(unittest.TestCase):
+    def module(self, value, info, **exports):
+        instance = MockInstance({"f": lambda: value, **exports})
+        instance.write(16, b"oops\0")
+        instance.write(32, b"hello")
+        instance.write(48, struct.pack("<3I", 1, 2, 3))
+        return host.Module(instance, {"f": info}), instance
+
+    def decode(self, value, info, **exports):
+        return self.module(value, info, **exports)[0].f()
+
+    def test_numbers(self):
+        self.assertEqual(self.decode(4.0, info(host.IDENTITY, array_type=4)), 4)
+        self.assertIsInstance(self.decode(4.0, info(host.IDENTITY, array_type=4)), int)
+        self.assertEqual(self.decode(2.5, info(host.IDENTITY, array_type=9)), 2.5)
+        self.assertEqual(self.decode(f64(2**64 - 1), info(host.AS_U64)), 2**64 - 1)
+        self.assertEqual(self.decode(f64(2**64 - 1), info(host.AS_I64)), -1)
+        self.assertIs(self.decode(1.0, info(host.BOOL)), True)
+        self.assertIsNone(self.decode(0.0, info(host.VOID)))
+
+    def test_packed_arrays(self):
+        self.assertEqual(list(self.decode(pair(7, 8), info(host.U32_PAIR))), [7, 8])
+        self.assertEqual(list(self.decode(f64(0xFF01), info(host.I8_OCTET))), [1, -1, 0, 0, 0, 0, 0, 0])
+
+    def test_arrays(self):
+        self.assertEqual(self.decode(pair(32, 5), info(host.STRING, array_type=0, is_array=1)), "hello")
+        self.assertEqual(list(self.decode(pair(48, 3), info(host.IDENTITY, array_type=4, is_array=1))), [1, 2, 3])
+        self.assertEqual(self.decode(pair(32, 5), info(host.MSGPACK, array_type=0, is_array=1)), b"hello")
+
+    def test_arrays_without_an_array_type_are_bytes(self):
+        self.assertEqual(list(self.decode(pair(32, 2), info(host.IDENTITY, array_type=10, is_array=1))), [104, 101])
+        self.assertEqual(self.decode(pair(32, 5), info(host.STRING, array_type=10, is_array=1)), "hello")
+
+    def test_none_and_err(self):
+        nan = 0xFFF80000
+        self.assertIsNone(self.decode(pair(0, nan), info(host.IDENTITY, is_option=1)))
+        self.assertIsNone(self.decode(pair(1, 0), info(host.STRING, array_type=0, is_array=1, is_option=1)))
+        with self.assertRaisesRegex(host.RustError, "^oops$"):
+            self.decode(pair(16, nan), info(host.IDENTITY, is_result=1))
+        with self.assertRaisesRegex(host.RustError, "^oops$"):
+            self.decode(pair(1, 16), info(host.STRING, array_type=0, is_array=1, is_result=1))
+        # Ok values are decoded as usual
+        self.assertEqual(self.decode(pair(32, 5), info(host.STRING, array_type=0, is_array=1, is_result=1)), "hello")
+
+    def test_pinned_values_are_released(self):
+        released = []
+        value = self.decode(
+            7.0,
+            info(host.STRING, array_type=0, is_array=1, is_pinned=1),
+            to_js_pinned_value=lambda handle: pair(32, 5),
+            to_js_release=released.append,
+        )
+        self.assertEqual(value, "hello")
+        self.assertEqual(released, [7])
+
+    def test_iterators_are_collected_and_dropped(self):
+        chunks = [pair(48, 2), pair(56, 1), pair(0, 0)]
+        dropped = []
+        value = self.decode(
+            3.0,
+            info(host.ITER, array_type=4),
+            to_js_iter_next=lambda handle: chunks.pop(0),
+            to_js_iter_drop=dropped.append,
+        )
+        self.assertEqual(list(value), [1, 2, 3])
+        self.assertEqual(dropped, [3])
+
+    def test_unsupported_values(self):
+        with self.assertRaises(NotImplementedError):
+            self.decode(0.0, bytes([0, 0, 0, 10, 9, 1, 0, 0]))
+        with self.assertRaises(AttributeError):
+            self.module(0.0, info(host.IDENTITY))[0].g
+
+
+class AbiVersionTest(unittest.TestCase):
+    def test_mismatch(self):
+        with self.assertRaisesRegex(RuntimeError, "uses version 1$"):
+            host.Module(MockInstance({}, abi_version=1), {})
+
+    def test_missing(self):
+        with self.assertRaisesRegex(RuntimeError, "does not export one"):
+            host.Module(MockInstance({}, abi_version=None), {})
+
+
+@unittest.skipUnless(os.environ.get("TO_JS_PYTHON"), "TO_JS_PYTHON is not set")
+class GeneratedTest(unittest.TestCase):
+    def test_generated_module(self):
+        generated = load(os.environ["TO_JS_PYTHON"], "generated")
+        self.assertEqual(
+            generated.INFO,
+            {"add": info(host.IDENTITY, array_type=4), "text": info(host.STRING, array_type=0, is_array=1)},
+        )
+        instance = MockInstance({"add": lambda x, y: x + y, "text": lambda: pair(32, 5)})
+        instance.write(32, b"hello")
+        rs = generated.Module(instance)
+        self.assertEqual(rs.add(2, 2), 4)
+        self.assertEqual(rs.text(), "hello")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,72 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { glue, mockInstance, info, f64, write, ArrayType } from "./mock.mjs";
+
+// A mock instance with a function that calls the JsFn passed as its second argument with each number below its
+// first argument (as the Rust side would, through the to_js_invoke trampoline), and a function without callbacks
+function mockCallbacks() {
+	const handlers = {};
+	let unwound = 0;
+	const u32 = f64(info({ arrayType: ArrayType.U32 }));
+	const instance = mockInstance({
+		count_matching: [
+			info({ arrayType: ArrayType.U32, convertsArgs: true }),
+			(n, index) => {
+				const pairs = new Float64Array(instance.exports.memory.buffer, 512, 2);
+				let count = 0;
+				for (let i = 0; i < n; i++) {
+					pairs.set([i, u32]);
+					count += handlers.to_js_invoke(index, 512, 1);
+				}
+				return count;
+			},
+		],
+		add: [info({ arrayType: ArrayType.U32 }), (a, b) => (received.push(a, b), a + b)],
+	}, { to_js_unwind: () => unwound++ });
+	const received = [];
+	return { instance, handlers, received, unwound: () => unwound };
+}
+
+for (const compile of [true, false]) {
+	test(`functions are registered for the duration of the call (compile: ${compile})`, () => {
+		const { instance, handlers } = mockCallbacks();
+		const rs = glue.wrap(instance, false, { handlers, compile });
+		assert.equal(rs.count_matching(10, (i) => i % 3 === 0), 4);
+		// The table is truncated once the call returns, so the next call receives the same index
+		assert.equal(rs.count_matching(5, (i) => i > 1), 3);
+	});
+
+	test(`the arguments of functions without callbacks are passed through unchanged (compile: ${compile})`, () => {
+		const { instance, handlers, received } = mockCallbacks();
+		const rs = glue.wrap(instance, false, { handlers, compile });
+		const fn = () => 3;
+		assert.equal(rs.add(2, 3), 5);
+		rs.add(2, fn);
+		assert.deepEqual(received, [2, 3, 2, fn]);
+	});
+}
+
+test("exceptions thrown by callbacks unwind the Rust call into JavaScript", () => {
+	const { instance, handlers, unwound } = mockCallbacks();
+	const rs = glue.wrap(instance, false, { handlers });
+	assert.throws(() => rs.count_matching(10, (i) => {
+		if (i === 3) throw new Error("oops");
+		return true;
+	}), /oops/);
+	assert.equal(unwound(), 1);
+	assert.equal(rs.count_matching(2, () => true), 2);
+	assert.equal(unwound(), 1);
+});
+
+test("exceptions thrown by imported functions unwind the Rust call into JavaScript", () => {
+	const handlers = {};
+	let unwound = 0;
+	const instance = mockInstance({}, {
+		// The type info of the import's argument, as a (ptr, len) pair of f64s
+		to_js_import_fail: () => write(instance, 256, new Float64Array([f64(info({ arrayType: ArrayType.U32 }))])),
+		to_js_unwind: () => unwound++,
+	});
+	glue.wrap(instance, false, { handlers, imports: { fail: (x) => { throw new Error(`failed with ${x}`); } } });
+	assert.throws(() => handlers.fail(7), /failed with 7/);
+	assert.equal(unwound, 1);
+});
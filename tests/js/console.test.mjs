@@ -0,0 +1,29 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { glue, mockInstance, write } from "./mock.mjs";
+
+test("messages logged from Rust go to the console method for their level", (t) => {
+	const handlers = {};
+	const instance = mockInstance();
+	glue.wrap(instance, false, { handlers });
+	const methods = Object.fromEntries(["error", "warn", "log", "debug"].map((name) => [name, t.mock.method(console, name, () => {})]));
+
+	const message = new TextEncoder().encode("hello");
+	write(instance, 64, message);
+	for (let level = 1; level <= 5; level++) handlers.to_js_console(level, 64, message.length);
+
+	const calls = (name) => methods[name].mock.calls.map((call) => call.arguments);
+	assert.deepEqual(calls("error"), [["hello"]]);
+	assert.deepEqual(calls("warn"), [["hello"]]);
+	assert.deepEqual(calls("log"), [["hello"]]);
+	assert.deepEqual(calls("debug"), [["hello"], ["hello"]]);
+});
+
+test("setLogLevel passes the index of the level to to_js_set_log_level", () => {
+	const levels = [];
+	const rs = glue.wrap(mockInstance({}, { to_js_set_log_level: (level) => levels.push(level) }));
+	for (const level of ["off", "error", "warn", "info", "debug", "trace"]) rs.setLogLevel(level);
+	assert.deepEqual(levels, [0, 1, 2, 3, 4, 5]);
+	assert.throws(() => rs.setLogLevel("verbose"), /Unknown log level: verbose/);
+	assert.equal(levels.length, 6);
+});
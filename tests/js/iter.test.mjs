@@ -0,0 +1,74 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { glue, mockInstance, info, write, pair, ArrayType, Transform } from "./mock.mjs";
+
+const iter = info({ arrayType: ArrayType.U32, transform: Transform.Iter });
+const HANDLE = 7;
+
+// Wrap a mock instance with a function `numbers` that returns a JsIter over the given chunks. Like the Rust side,
+// which frees each chunk when pulling the next, every chunk is written to the same location.
+function numbers(chunks, alwaysCopyData = false) {
+	const calls = { next: 0, drop: [] };
+	const instance = mockInstance(
+		{ numbers: [iter, () => HANDLE] },
+		{
+			to_js_iter_next(handle) {
+				assert.equal(handle, HANDLE);
+				const chunk = chunks[calls.next++] ?? [];
+				return write(instance, 64, new Uint32Array(chunk));
+			},
+			to_js_iter_drop: (handle) => calls.drop.push(handle),
+		},
+	);
+	return { rs: glue.wrap(instance, alwaysCopyData), calls };
+}
+
+test("iterators can be collected across chunks", () => {
+	const { rs, calls } = numbers([[1, 2], [3, 4], [5]]);
+	const chunks = Array.from(rs.numbers());
+	assert.deepEqual(chunks, [new Uint32Array([1, 2]), new Uint32Array([3, 4]), new Uint32Array([5])]);
+	assert.deepEqual([...rs.numbers()], []);
+	assert.equal(calls.next, 5);
+});
+
+test("exhausted iterators are dropped once", () => {
+	const { rs, calls } = numbers([[1, 2]]);
+	const it = rs.numbers();
+	assert.deepEqual(it.next(), { done: false, value: new Uint32Array([1, 2]) });
+	assert.deepEqual(it.next(), { done: true, value: undefined });
+	assert.deepEqual(it.next(), { done: true, value: undefined });
+	assert.equal(calls.next, 2);
+	assert.deepEqual(calls.drop, [HANDLE]);
+});
+
+test("iterators are dropped when iteration ends early", () => {
+	const { rs, calls } = numbers([[1], [2], [3]]);
+	for (const chunk of rs.numbers()) {
+		assert.deepEqual(chunk, new Uint32Array([1]));
+		break;
+	}
+	assert.equal(calls.next, 1);
+	assert.deepEqual(calls.drop, [HANDLE]);
+
+	const it = rs.numbers();
+	it.return();
+	it.return();
+	assert.deepEqual(it.next(), { done: true, value: undefined });
+	assert.deepEqual(calls.drop, [HANDLE, HANDLE]);
+});
+
+test("iterator views refer to WebAssembly memory", () => {
+	const { rs, calls } = numbers([[1, 2], [3, 4]]);
+	const views = [];
+	for (const view of rs.numbers().views()) {
+		assert.equal(view.byteOffset, 64);
+		views.push(view);
+	}
+	// Each view is only valid until the next chunk is pulled, which here overwrites it
+	assert.deepEqual(views, [new Uint32Array([3, 4]), new Uint32Array([3, 4])]);
+	assert.deepEqual(calls.drop, [HANDLE]);
+
+	const copies = numbers([[1, 2]], true).rs.numbers().views().next().value;
+	assert.equal(copies.byteOffset, 0);
+	assert.deepEqual(copies, new Uint32Array([1, 2]));
+});
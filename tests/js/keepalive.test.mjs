@@ -0,0 +1,34 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { glue, mockInstance, info, ArrayType } from "./mock.mjs";
+
+test("pinned values are released once", () => {
+	const released = [];
+	const instance = mockInstance(
+		{ get: [info({ arrayType: ArrayType.U32, pinned: true }), () => 42] },
+		{ to_js_pinned_value: (handle) => handle + 1, to_js_release: (handle) => released.push(handle) }
+	);
+	for (const compile of [true, false]) {
+		const rs = glue.wrap(instance, false, { compile });
+		const pinned = rs.get();
+		assert.equal(pinned.value, 43);
+		pinned.release();
+		pinned.release();
+	}
+	assert.deepEqual(released, [42, 42]);
+});
+
+test("scopes are exited when their function throws", () => {
+	const calls = [];
+	const instance = mockInstance(
+		{ get: [info({ arrayType: ArrayType.U32 }), () => (calls.push("get"), 1)] },
+		{ to_js_scope_enter: () => calls.push("enter"), to_js_scope_exit: () => calls.push("exit") }
+	);
+	const rs = glue.wrap(instance);
+	assert.equal(rs.scope(() => rs.get() + rs.get()), 2);
+	assert.throws(() => rs.scope(() => {
+		rs.get();
+		throw new Error("oops");
+	}), /oops/);
+	assert.deepEqual(calls, ["enter", "get", "get", "exit", "enter", "get", "exit"]);
+});
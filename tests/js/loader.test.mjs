@@ -0,0 +1,40 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { readFileSync } from "node:fs";
+import { pathToFileURL } from "node:url";
+import { load, wrapSync } from "../../src/loader.js";
+
+// The module built by tests/node.rs, which embeds the glue code like a module built with to_js
+const fixture = process.env.TO_JS_FIXTURE;
+const skip = fixture === undefined && "TO_JS_FIXTURE is not set (run through `cargo test --test node`)";
+
+// The fixture's imports, for which `imported` doubles its argument and `offset` is 1
+const options = { imports: { imported: (x) => x * 2 }, importObject: { env: { offset: () => 1 } } };
+
+for (const [kind, source] of [
+	["a path", () => fixture],
+	["a file URL", () => pathToFileURL(fixture)],
+	["bytes", () => readFileSync(fixture)],
+	["an ArrayBuffer", () => new Uint8Array(readFileSync(fixture)).buffer],
+]) {
+	test(`loads a module from ${kind}`, { skip }, async () => {
+		const rs = await load(source(), options);
+		assert.equal(rs.add(2, 3), 5);
+		assert.equal(typeof rs.mod.wrap, "function");
+	});
+}
+
+test("forwards imports to their implementations", { skip }, async () => {
+	const rs = await load(fixture, options);
+	assert.equal(rs.add_imported(3), 7);
+});
+
+test("wraps an instantiated module synchronously", { skip }, async () => {
+	const handlers = {};
+	const to_js = { imported: (...args) => handlers.imported(...args) };
+	const { instance } = await WebAssembly.instantiate(readFileSync(fixture), { ...options.importObject, to_js });
+	const rs = wrapSync(instance, false, { imports: options.imports, handlers });
+	assert.equal(rs.add(2, 3), 5);
+	assert.equal(rs.add_imported(3), 7);
+	assert.equal(typeof rs.mod.wrap, "function");
+});
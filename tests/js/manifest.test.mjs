@@ -0,0 +1,47 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { readFileSync } from "node:fs";
+import { glue, mockInstance, info, write, ArrayType } from "./mock.mjs";
+
+// A copy of the module built by tests/node.rs without `_info_` exports, made by the to_js binary
+const stripped = process.env.TO_JS_STRIPPED;
+const skip = stripped === undefined && "TO_JS_STRIPPED is not set (run through `cargo test --test node`)";
+
+test("reads the type info from the manifest exported by the instance", { skip }, async () => {
+	const handlers = {};
+	const to_js = { imported: (...args) => handlers.imported(...args) };
+	const source = await WebAssembly.instantiate(readFileSync(stripped), { env: { offset: () => 1 }, to_js });
+	assert.equal(source.instance.exports.add_info_, undefined);
+	for (const instance of [source, source.instance]) {
+		const rs = glue.wrap(instance, false, { imports: { imported: (x) => x * 2 }, handlers });
+		assert.equal(rs.add(2, 3), 5);
+		assert.equal(rs.add_imported(3), 7);
+	}
+});
+
+// A manifest entry (see manifest.rs) written to memory at `offset`, exported like a static as a global holding its
+// address
+function entry(instance, offset, kind, name, infos) {
+	const bytes = [kind, ...new Uint8Array(new Uint32Array([name.length]).buffer), ...new TextEncoder().encode(name)];
+	bytes.push(infos.length, ...infos.flatMap((x) => [...x]));
+	write(instance, offset, bytes);
+	const prefix = kind === 0 ? "export" : "import";
+	instance.exports[`to_js_manifest_${prefix}_${name}`] = new WebAssembly.Global({ value: "i32" }, offset);
+}
+
+test("the manifest is preferred to info exports, and is copied out of memory", () => {
+	const instance = mockInstance({ bytes: [info(), () => write(instance, 512, new Uint8Array([1, 2]))] });
+	entry(instance, 64, 0, "bytes", [info({ array: true, arrayType: ArrayType.U8 })]);
+	entry(instance, 128, 1, "imported", [info({ arrayType: ArrayType.U32 })]);
+	const handlers = {};
+	const rs = glue.wrap(instance, false, { imports: { imported: (x) => x }, handlers });
+	// The entries are no longer needed once they have been read
+	new Uint8Array(instance.exports.memory.buffer, 64, 128).fill(0);
+	assert.deepEqual(rs.bytes(), new Uint8Array([1, 2]));
+	assert.equal(typeof handlers.imported, "function");
+});
+
+test("requires a manifest or info exports", () => {
+	const instance = mockInstance({}, { to_js_no_info_exports: () => {} });
+	assert.throws(() => glue.wrap(instance), /does not export a manifest/);
+});
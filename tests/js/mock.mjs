@@ -0,0 +1,56 @@
+// Helpers for testing the glue code (lib.js) against mock instances, whose exports are JavaScript functions that
+// return values in the same encoding as the exports of a module built with to_js.
+//
+// The glue code is read from the path in TO_JS_GLUE if it is set (eg. to test a minified copy; see tests/node.rs),
+// and from src/lib.js otherwise.
+
+import { readFileSync } from "node:fs";
+
+export const gluePath = process.env.TO_JS_GLUE ?? new URL("../../src/lib.js", import.meta.url);
+const code = readFileSync(gluePath, "utf8");
+export const glue = new Function(`${code}\nreturn { wrap, createClass, wrapWorker };`)();
+
+// The ABI version that the glue code decodes, which mock instances report (see ABI_VERSION in typeinfo.rs)
+export const ABI_VERSION = Number(/const ABI_VERSION\s*=\s*(\d+)/.exec(code)[1]);
+
+// In enum variant order (enum: ArrayType and enum: Transform)
+export const ArrayType = { U8: 0, I8: 1, U16: 2, I16: 3, U32: 4, I32: 5, F32: 6, U64: 7, I64: 8, F64: 9, None: 10 };
+export const Transform = {
+	U8Octet: 0, I8Octet: 1, U16Quartet: 2, I16Quartet: 3, U32Pair: 4, I32Pair: 5, F32Pair: 6, AsU64: 7, AsI64: 8,
+	Identity: 9, Void: 10, Bool: 11, String: 12, Json: 13, MsgPack: 14, Structured: 15, Iter: 16, ViewRef: 17,
+};
+
+// The 8 bytes of an Info (see typeinfo.rs)
+export function info({ result = false, option = false, array = false, arrayType = ArrayType.None, transform = Transform.Identity, promise = false, pinned = false, convertsArgs = false } = {}) {
+	return new Uint8Array([+result, +option, +array, arrayType, transform, +promise, +pinned, +convertsArgs]);
+}
+
+// The f64 whose bytes are those of the given array
+export function f64(array) {
+	return new Float64Array(new Uint8Array(array.buffer, array.byteOffset, 8).slice().buffer)[0];
+}
+
+// The f64 encoding of a (low, high) pair of u32s, such as a (pointer, length) pair
+export function pair(low, high) {
+	return f64(new Uint32Array([low, high]));
+}
+
+// Return a mock instance with the given functions, each of which is a [info, implementation] pair, and with the
+// given additional exports. A companion `_info_` export is added for each function.
+export function mockInstance(functions = {}, exports = {}) {
+	const memory = new WebAssembly.Memory({ initial: 1 });
+	const instance = { exports: { memory, to_js_abi_version: () => ABI_VERSION, ...exports } };
+	for (const [name, [typeInfo, fn]] of Object.entries(functions)) {
+		instance.exports[name] = fn;
+		instance.exports[`${name}_info_`] = () => f64(typeInfo);
+	}
+	return instance;
+}
+
+// Write bytes into an instance's memory at the given offset, returning the (pointer, length) pair that refers to them
+// (with the length in elements if the data is a typed array)
+export function write(instance, offset, data) {
+	const bytes = ArrayBuffer.isView(data) ? new Uint8Array(data.buffer, data.byteOffset, data.byteLength) : new Uint8Array(data);
+	new Uint8Array(instance.exports.memory.buffer).set(bytes, offset);
+	return pair(offset, ArrayBuffer.isView(data) ? data.length : bytes.length);
+}
@@ -0,0 +1,76 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { glue, mockInstance, info, write, ArrayType, Transform } from "./mock.mjs";
+
+const msgpack = info({ array: true, arrayType: ArrayType.U8, transform: Transform.MsgPack });
+
+// Wrap a mock instance with a single function `value` that returns the given MessagePack-encoded bytes
+function decode(bytes, alwaysCopyData = false) {
+	const instance = mockInstance({ value: [msgpack, () => encoded] });
+	const encoded = write(instance, 64, bytes);
+	return glue.wrap(instance, alwaysCopyData).value();
+}
+
+test("MessagePack scalars", () => {
+	assert.equal(decode([0xc0]), null);
+	assert.equal(decode([0xc2]), false);
+	assert.equal(decode([0xc3]), true);
+	assert.equal(decode([0x7f]), 127);
+	assert.equal(decode([0xff]), -1);
+	assert.equal(decode([0xcc, 0xff]), 255);
+	assert.equal(decode([0xcd, 0x01, 0x00]), 256);
+	assert.equal(decode([0xce, 0xff, 0xff, 0xff, 0xff]), 0xffffffff);
+	assert.equal(decode([0xd0, 0x80]), -128);
+	assert.equal(decode([0xd1, 0x80, 0x00]), -32768);
+	assert.equal(decode([0xd2, 0x80, 0x00, 0x00, 0x00]), -(2 ** 31));
+	assert.equal(decode([0xca, 0x3f, 0xc0, 0x00, 0x00]), 1.5);
+	assert.equal(decode([0xcb, 0x40, 0x09, 0x21, 0xfb, 0x54, 0x44, 0x2d, 0x18]), Math.PI);
+});
+
+test("MessagePack 64-bit integers decode as BigInts", () => {
+	assert.equal(decode([0xcf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]), 2n ** 64n - 1n);
+	assert.equal(decode([0xd3, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe]), -2n);
+});
+
+test("MessagePack strings", () => {
+	assert.equal(decode([0xa2, 0x68, 0x69]), "hi");
+	assert.equal(decode([0xd9, 0x03, 0xe2, 0x82, 0xac]), "€");
+	assert.equal(decode([0xda, 0x00, 0x01, 0x41]), "A");
+	assert.equal(decode([0xdb, 0x00, 0x00, 0x00, 0x00]), "");
+});
+
+test("MessagePack arrays and maps", () => {
+	// {"a": 1, "b": [true, null, -1]}
+	assert.deepEqual(decode([0x82, 0xa1, 0x61, 0x01, 0xa1, 0x62, 0x93, 0xc3, 0xc0, 0xff]), { a: 1, b: [true, null, -1] });
+	assert.deepEqual(decode([0xdc, 0x00, 0x02, 0x01, 0x02]), [1, 2]);
+	assert.deepEqual(decode([0xde, 0x00, 0x01, 0x01, 0xa1, 0x78]), { 1: "x" });
+	assert.deepEqual(decode([0x90]), []);
+	assert.deepEqual(decode([0x80]), {});
+});
+
+test("MessagePack maps with a __proto__ key", () => {
+	// {"__proto__": {"x": 1}}
+	const obj = decode([0x81, 0xa9, ...new TextEncoder().encode("__proto__"), 0x81, 0xa1, 0x78, 0x01]);
+	assert.equal(Object.getPrototypeOf(obj), Object.prototype);
+	assert.deepEqual(Object.keys(obj), ["__proto__"]);
+	assert.deepEqual(Object.getOwnPropertyDescriptor(obj, "__proto__").value, { x: 1 });
+	assert.equal(obj.x, undefined);
+});
+
+test("MessagePack byte strings are views unless alwaysCopyData is true", () => {
+	const view = decode([0xc4, 0x03, 1, 2, 3]);
+	assert.deepEqual(view, new Uint8Array([1, 2, 3]));
+	assert.equal(view.buffer.byteLength, 65536);
+	const copy = decode([0xc5, 0x00, 0x02, 4, 5], true);
+	assert.deepEqual(copy, new Uint8Array([4, 5]));
+	assert.equal(copy.buffer.byteLength, 2);
+});
+
+test("MessagePack extension types", () => {
+	assert.deepEqual(decode([0xd4, 0x05, 0x2a], true), { type: 5, data: new Uint8Array([0x2a]) });
+	assert.deepEqual(decode([0xc7, 0x02, 0xff, 0x01, 0x02], true), { type: -1, data: new Uint8Array([1, 2]) });
+});
+
+test("MessagePack rejects unsupported type bytes", () => {
+	assert.throws(() => decode([0xc1]), /unsupported type byte 0xc1/);
+});
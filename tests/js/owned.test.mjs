@@ -0,0 +1,73 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { glue, mockInstance, info, write, pair, ArrayType } from "./mock.mjs";
+
+// A mock debug build whose allocator grows memory, with a function that returns a typed array view and one that
+// takes an Owned array
+function mockOwned() {
+	const received = [];
+	const instance = mockInstance(
+		{
+			bytes: [info({ array: true, arrayType: ArrayType.U8 }), () => write(instance, 64, new Uint8Array([1, 2, 3]))],
+			ingest: [info({ arrayType: ArrayType.U32, convertsArgs: true }), (x) => (received.push(x), 0)],
+		},
+		{
+			to_js_debug_assertions: () => 1,
+			to_js_alloc_bytes: () => {
+				instance.exports.memory.grow(1);
+				return 1024;
+			},
+		}
+	);
+	return { instance, received };
+}
+
+test("allocArray checks for views detached by memory growth", async (t) => {
+	const warn = t.mock.method(console, "warn", () => {});
+	t.mock.timers.enable({ apis: ["setTimeout"] });
+	const { instance } = mockOwned();
+	const rs = glue.wrap(instance);
+	const view = rs.bytes();
+	const data = rs.allocArray(Float64Array, 4);
+	data.array.fill(1);
+	await new Promise((resolve) => setImmediate(resolve));
+	t.mock.timers.tick(1000);
+	assert.equal(warn.mock.callCount(), 1);
+	assert.equal(view.length, 0);
+});
+
+test("allocated arrays are passed to Rust as (ptr, len) pairs", () => {
+	const { instance, received } = mockOwned();
+	const rs = glue.wrap(instance);
+	const data = rs.allocArray(Float64Array, 4);
+	rs.ingest(data);
+	assert.deepEqual(received, [pair(1024, 4)]);
+	assert.throws(() => data.array, /moved to Rust/);
+	assert.throws(() => rs.ingest(data), /already been moved/);
+});
+
+test("allocArray only allocates typed arrays", () => {
+	const rs = glue.wrap(mockOwned().instance);
+	assert.throws(() => rs.allocArray(Array, 4), /expected a typed array type, got Array/);
+	assert.throws(() => rs.allocArray(DataView, 4), /expected a typed array type/);
+});
+
+test("allocArray throws a RangeError for sizes that cannot be allocated", () => {
+	const sizes = [];
+	const instance = mockInstance(
+		{},
+		{
+			// Like Rust, return a null pointer for sizes over isize::MAX
+			to_js_alloc_bytes: (size) => (sizes.push(size), size > 0x7fffffff ? 0 : 64),
+		}
+	);
+	const rs = glue.wrap(instance);
+	assert.throws(() => rs.allocArray(Float64Array, 0x10000000), RangeError);
+	assert.deepEqual(sizes, [0x80000000]);
+	// Sizes that would wrap around are not passed to Rust
+	for (const length of [-1, 1.5, 2 ** 30, NaN]) {
+		assert.throws(() => rs.allocArray(Uint32Array, length), /cannot allocate/);
+	}
+	assert.deepEqual(sizes, [0x80000000]);
+	assert.equal(rs.allocArray(Uint32Array, 2).array.length, 2);
+});
@@ -0,0 +1,34 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { glue, mockInstance, write } from "./mock.mjs";
+
+test("spans are measured and events are marked with the Performance API", (t) => {
+	const handlers = {};
+	const instance = mockInstance();
+	glue.wrap(instance, false, { handlers });
+	const measure = t.mock.method(performance, "measure", () => {});
+	const mark = t.mock.method(performance, "mark", () => {});
+	let now = 0;
+	t.mock.method(performance, "now", () => now);
+
+	write(instance, 64, new TextEncoder().encode("outer"));
+	write(instance, 96, new TextEncoder().encode("inner"));
+	// Span ids that only differ above 32 bits are distinct
+	const [outerId, innerId] = [1n << 32n, 1n << 33n];
+	now = 10;
+	handlers.to_js_performance(0, outerId, 64, 5);
+	now = 20;
+	handlers.to_js_performance(0, innerId, 96, 5);
+	handlers.to_js_performance(1, innerId, 96, 5);
+	handlers.to_js_performance(2, 0n, 96, 5);
+	handlers.to_js_performance(1, outerId, 64, 5);
+
+	assert.deepEqual(
+		measure.mock.calls.map((call) => call.arguments),
+		[
+			["inner", { start: 20 }],
+			["outer", { start: 10 }],
+		],
+	);
+	assert.deepEqual(mark.mock.calls.map((call) => call.arguments), [["inner"]]);
+});
@@ -0,0 +1,27 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { readFileSync } from "node:fs";
+import { pathToFileURL } from "node:url";
+
+// The glue code generated by the to_js binary for the module built by tests/node.rs
+const precompiled = process.env.TO_JS_PRECOMPILED;
+const skip = precompiled === undefined && "TO_JS_PRECOMPILED is not set (run through `cargo test --test node`)";
+
+for (const module of ["TO_JS_FIXTURE", "TO_JS_STRIPPED"]) {
+	test(`wraps the module with the generated wrappers (${module})`, { skip }, async () => {
+		const { wrap } = await import(pathToFileURL(precompiled));
+		const handlers = {};
+		const to_js = { imported: (...args) => handlers.imported(...args) };
+		const env = { offset: () => 1 };
+		const { instance } = await WebAssembly.instantiate(readFileSync(process.env[module]), { env, to_js });
+		const rs = wrap(instance, false, { imports: { imported: (x) => x * 2 }, handlers });
+		assert.equal(rs.add(2, 3), 5);
+		assert.equal(rs.add_imported(3), 7);
+		assert.throws(() => rs.add(1), /add: expected 2 arguments, got 1/);
+	});
+}
+
+test("does not include the code that generates wrappers at runtime", { skip }, () => {
+	const source = readFileSync(precompiled, "utf8");
+	assert.doesNotMatch(source, /new Function|wrapperSource|closureWrapper/);
+});
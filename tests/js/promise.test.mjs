@@ -0,0 +1,49 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { glue, mockInstance, info, write, pair } from "./mock.mjs";
+
+// A mock instance with two async functions, whose tasks complete when they are listed in `completed`
+function mockAsync() {
+	let completed = [];
+	const instance = mockInstance(
+		{
+			first: [info({ promise: true }), () => 1],
+			second: [info({ promise: true }), () => 2],
+		},
+		{
+			to_js_poll: () => (completed.length === 0 ? pair(256, 0) : write(instance, 256, new Uint32Array(completed.splice(0)))),
+			to_js_task_result: (handle) => handle * 10,
+		}
+	);
+	return { instance, complete: (...handles) => completed.push(...handles) };
+}
+
+test("promises resolve once their tasks complete", async () => {
+	const { instance, complete } = mockAsync();
+	const rs = glue.wrap(instance);
+	const first = rs.first();
+	const second = rs.second();
+	complete(2, 1);
+	assert.deepEqual(await Promise.all([first, second]), [10, 20]);
+});
+
+test("polling skips the handles of tasks that this wrapper did not create", async () => {
+	const { instance, complete } = mockAsync();
+	const rs = glue.wrap(instance);
+	const first = rs.first();
+	const second = rs.second();
+	complete(7, 1, 2);
+	assert.deepEqual(await Promise.all([first, second]), [10, 20]);
+});
+
+test("exports hide the functions provided by wrap, with a warning", (t) => {
+	const warn = t.mock.method(console, "warn", () => {});
+	for (const name of ["poll", "scope", "stats", "setLogLevel", "allocArray"]) {
+		const rs = glue.wrap(mockInstance({ [name]: [info(), () => 3] }));
+		assert.equal(rs[name](), 3);
+		assert.match(warn.mock.calls.at(-1).arguments[0], new RegExp(`export \`${name}\` hides`));
+	}
+	assert.equal(warn.mock.callCount(), 5);
+	const rs = glue.wrap(mockInstance({ toString: [info(), () => 5] }));
+	assert.equal(rs.toString(), 5);
+});
@@ -0,0 +1,16 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { glue, mockInstance, write } from "./mock.mjs";
+
+test("stats are decoded from the JSON returned by to_js_stats", () => {
+	const json = `{"keepaliveCount":1,"keepaliveBytes":2,"peakKeepaliveCount":3,"peakKeepaliveBytes":4,"memoryPages":5,"allocs":{"a::B":6}}`;
+	const instance = mockInstance({}, { to_js_stats: () => write(instance, 64, new TextEncoder().encode(json)) });
+	assert.deepEqual(glue.wrap(instance).stats(), {
+		keepaliveCount: 1,
+		keepaliveBytes: 2,
+		peakKeepaliveCount: 3,
+		peakKeepaliveBytes: 4,
+		memoryPages: 5,
+		allocs: { "a::B": 6 },
+	});
+});
@@ -0,0 +1,65 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { glue, mockInstance, info, write, ArrayType, Transform } from "./mock.mjs";
+
+const structured = info({ array: true, arrayType: ArrayType.U8, transform: Transform.Structured });
+
+// Encode a value in the format produced by the serializer in ser.rs, from its data section and op stream
+function encoding(data, ops) {
+	const bytes = new Uint8Array(8 + data.length + ops.length);
+	const view = new DataView(bytes.buffer);
+	view.setUint32(0, data.length, true);
+	view.setUint32(4, ops.length, true);
+	bytes.set(data, 8);
+	bytes.set(ops, 8 + data.length);
+	return bytes;
+}
+
+const u32 = (x) => [...new Uint8Array(new Uint32Array([x]).buffer)];
+const str = (s) => [6, ...u32(s.length), ...new TextEncoder().encode(s)];
+
+function decode(bytes, alwaysCopyData = false, setup = () => {}) {
+	const instance = mockInstance({ value: [structured, () => encoded] });
+	setup(instance);
+	// The encoding is 8-byte aligned, as it is stored as u64 words
+	const encoded = write(instance, 64, bytes);
+	return glue.wrap(instance, alwaysCopyData).value();
+}
+
+test("Structured scalars, strings, arrays, and objects", () => {
+	const f64 = [...new Uint8Array(new Float64Array([1.5]).buffer)];
+	const u64 = [...new Uint8Array(new BigUint64Array([2n ** 60n]).buffer)];
+	const i64 = [...new Uint8Array(new BigInt64Array([-(2n ** 60n)]).buffer)];
+	const ops = [8, ...u32(3), ...str("a"), 7, ...u32(4), 0, 1, 2, 3, ...f64, ...str("b"), 4, ...u64, ...str("c"), 5, ...i64];
+	assert.deepEqual(decode(encoding([], ops)), { a: [null, false, true, 1.5], b: 2n ** 60n, c: -(2n ** 60n) });
+});
+
+test("Structured objects with a __proto__ key", () => {
+	// {"__proto__": {"x": true}}
+	const obj = decode(encoding([], [8, ...u32(1), ...str("__proto__"), 8, ...u32(1), ...str("x"), 2]));
+	assert.equal(Object.getPrototypeOf(obj), Object.prototype);
+	assert.deepEqual(Object.keys(obj), ["__proto__"]);
+	assert.deepEqual(Object.getOwnPropertyDescriptor(obj, "__proto__").value, { x: true });
+	assert.equal(obj.x, undefined);
+});
+
+test("Structured numeric sequences are typed array views of the data section", () => {
+	const data = [...new Uint8Array(new Float64Array([0.5, 0.25]).buffer)];
+	const value = decode(encoding(data, [9, ArrayType.F64, ...u32(0), ...u32(2)]));
+	assert.deepEqual(value, new Float64Array([0.5, 0.25]));
+	assert.equal(value.byteOffset, 64 + 8);
+	const copy = decode(encoding(data, [9, ArrayType.F64, ...u32(0), ...u32(2)]), true);
+	assert.deepEqual(copy, new Float64Array([0.5, 0.25]));
+	assert.equal(copy.buffer.byteLength, 16);
+});
+
+test("Structured views refer to memory by pointer", () => {
+	const setup = (instance) => write(instance, 512, new Uint32Array([7, 8, 9]));
+	const ops = [7, ...u32(1), 10, ArrayType.U32, ...u32(516), ...u32(2)];
+	const [view] = decode(encoding([], ops), false, setup);
+	assert.deepEqual(view, new Uint32Array([8, 9]));
+	assert.equal(view.byteOffset, 516);
+	const [copy] = decode(encoding([], ops), true, setup);
+	assert.deepEqual(copy, new Uint32Array([8, 9]));
+	assert.equal(copy.buffer.byteLength, 8);
+});
@@ -0,0 +1,49 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { setFlagsFromString } from "node:v8";
+import { runInNewContext } from "node:vm";
+import { glue, mockInstance, info, write, ArrayType } from "./mock.mjs";
+
+// Expose the garbage collector, which the glue code asks to run before checking for detached views
+setFlagsFromString("--expose-gc");
+globalThis.gc ??= runInNewContext("gc");
+
+// A mock debug build with a function that returns a typed array view and one that grows memory
+function mockViews() {
+	const instance = mockInstance(
+		{
+			bytes: [info({ array: true, arrayType: ArrayType.U8 }), () => write(instance, 64, new Uint8Array([1, 2, 3]))],
+			grow: [info({ transform: 10 }), () => instance.exports.memory.grow(1)],
+		},
+		{ to_js_debug_assertions: () => 1 }
+	);
+	return instance;
+}
+
+const tick = () => new Promise((resolve) => setImmediate(resolve));
+
+test("warns about views that are still alive after memory growth", async (t) => {
+	const warn = t.mock.method(console, "warn", () => {});
+	t.mock.timers.enable({ apis: ["setTimeout"] });
+	const rs = glue.wrap(mockViews());
+	const view = rs.bytes();
+	await tick();
+	rs.grow();
+	await tick();
+	t.mock.timers.tick(1000);
+	assert.equal(warn.mock.callCount(), 1);
+	assert.match(warn.mock.calls[0].arguments[0], /detaching 1 typed array /);
+	assert.equal(view.length, 0);
+});
+
+test("does not warn about views that were dropped before memory growth", async (t) => {
+	const warn = t.mock.method(console, "warn", () => {});
+	t.mock.timers.enable({ apis: ["setTimeout"] });
+	const rs = glue.wrap(mockViews());
+	(() => rs.bytes().reduce((a, b) => a + b))();
+	await tick();
+	rs.grow();
+	await tick();
+	t.mock.timers.tick(1000);
+	assert.equal(warn.mock.callCount(), 0);
+});
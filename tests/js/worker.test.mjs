@@ -0,0 +1,129 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { readFileSync } from "node:fs";
+import { resolveObjectURL } from "node:buffer";
+import { pathToFileURL } from "node:url";
+import { Worker as NodeWorker } from "node:worker_threads";
+import { glue } from "./mock.mjs";
+
+// The module built by tests/node.rs for testing wrapWorker and the glue code generated for it, and the module for
+// testing the loader, which cannot be instantiated in a worker since it has `env` imports
+const fixture = process.env.TO_JS_WORKER_FIXTURE;
+const precompiled = process.env.TO_JS_WORKER_PRECOMPILED;
+const loaderFixture = process.env.TO_JS_FIXTURE;
+const skip = fixture === undefined && "TO_JS_WORKER_FIXTURE is not set (run through `cargo test --test node`)";
+
+// Shims for the Web Worker API on top of worker_threads. The worker is created from the source in the Blob that
+// `wrapWorker` creates, after a prelude that provides the worker's `addEventListener` and `postMessage`.
+const CRASH = "crash";
+const prelude = `
+	const { parentPort } = require("node:worker_threads");
+	// Throw an uncaught error when asked to, to test errors after initialization
+	parentPort.on("message", (data) => {
+		if (data === ${JSON.stringify(CRASH)}) throw new Error("crashed");
+	});
+	globalThis.addEventListener = (type, listener) =>
+		parentPort.on(type, (data) => data !== ${JSON.stringify(CRASH)} && listener({ data }));
+	// Browsers cannot transfer the buffer of a WebAssembly memory, and throw, while Node.js copies it. The memories
+	// of instances are recorded so that postMessage can throw likewise.
+	const memories = [];
+	const instantiate = WebAssembly.instantiate;
+	WebAssembly.instantiate = async (...args) => {
+		const result = await instantiate(...args);
+		memories.push((result.instance ?? result).exports.memory);
+		return result;
+	};
+	globalThis.postMessage = (data, transfer = []) => {
+		if (memories.some((memory) => transfer.includes(memory.buffer))) {
+			throw new DOMException("cannot transfer the buffer of a WebAssembly memory", "DataCloneError");
+		}
+		parentPort.postMessage(data, transfer);
+	};
+`;
+
+globalThis.Worker = class Worker {
+	// The most recently created worker
+	static last;
+
+	constructor(url) {
+		Worker.last = this;
+		this.node = resolveObjectURL(url).text().then((source) => {
+			const node = new NodeWorker(prelude + source, { eval: true });
+			node.on("message", (data) => this.onmessage?.({ data }));
+			node.on("error", (error) => this.onerror?.({ message: error.message }));
+			return node;
+		});
+	}
+
+	postMessage(data) {
+		this.node.then((node) => node.postMessage(data));
+	}
+
+	terminate() {
+		this.node.then((node) => node.terminate());
+	}
+
+	crash() {
+		this.node.then((node) => node.postMessage(CRASH));
+	}
+};
+
+const compile = (path) => WebAssembly.compile(readFileSync(path));
+
+test("calls functions in a worker", { skip }, async () => {
+	const rs = await glue.wrapWorker(await compile(fixture));
+	try {
+		for (const name of ["add", "bytes", "spin", "view", "terminate"]) assert.equal(typeof rs[name], "function");
+		assert.equal(await rs.add(2, 3), 5);
+		assert.deepEqual(await rs.bytes(), new TextEncoder().encode("hello"));
+	} finally {
+		rs.terminate();
+	}
+});
+
+test("calls functions in a worker with the generated glue code", { skip }, async () => {
+	const { wrapWorker } = await import(pathToFileURL(precompiled));
+	const rs = await wrapWorker(await compile(fixture));
+	try {
+		assert.equal(await rs.add(2, 3), 5);
+		assert.deepEqual(await rs.bytes(), new TextEncoder().encode("hello"));
+	} finally {
+		rs.terminate();
+	}
+});
+
+test("sends views of WebAssembly memory without transferring it", { skip }, async () => {
+	const rs = await glue.wrapWorker(await compile(fixture));
+	try {
+		const { view } = await rs.view();
+		assert.equal(new TextDecoder().decode(view), "hello");
+		// Only the view is copied, rather than the whole memory that it is a view of
+		assert.equal(view.buffer.byteLength, 5);
+		// The worker's memory is still usable
+		assert.deepEqual(await rs.bytes(), new TextEncoder().encode("hello"));
+	} finally {
+		rs.terminate();
+	}
+});
+
+test("rejects unfinished calls when terminated", { skip }, async () => {
+	const rs = await glue.wrapWorker(await compile(fixture));
+	const spinning = rs.spin();
+	rs.terminate();
+	await assert.rejects(spinning, /terminated/);
+	await assert.rejects(rs.add(1, 2), /terminated/);
+});
+
+test("rejects unfinished calls when the worker errors", { skip }, async () => {
+	const rs = await glue.wrapWorker(await compile(fixture));
+	try {
+		globalThis.Worker.last.crash();
+		await assert.rejects(rs.add(1, 2), /crashed/);
+	} finally {
+		rs.terminate();
+	}
+});
+
+test("rejects if the module cannot be instantiated", { skip }, async () => {
+	await assert.rejects(glue.wrapWorker(await compile(loaderFixture)), /import/i);
+});
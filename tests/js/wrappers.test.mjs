@@ -0,0 +1,33 @@
+import test from "node:test";
+import assert from "node:assert/strict";
+import { glue, mockInstance, info, pair, write, ArrayType } from "./mock.mjs";
+
+// The encodings of Result<Option<u32>, E> (with the HighBitsNaN niche; see niche.rs)
+const NAN_HIGH_BITS = 0xfff80000;
+const okNone = pair(0, NAN_HIGH_BITS);
+
+// A mock instance with a function that returns the given values in turn, and an error message at address 64
+function mockResults(...values) {
+	const instance = mockInstance({
+		get: [info({ result: true, option: true, arrayType: ArrayType.U32 }), () => values.shift()],
+	});
+	write(instance, 64, new TextEncoder().encode("oops\0"));
+	return instance;
+}
+
+for (const compile of [true, false]) {
+	test(`Result<Option<T>, E> decodes Ok(None) as null (compile: ${compile})`, () => {
+		const rs = glue.wrap(mockResults(okNone, 7, pair(64, NAN_HIGH_BITS)), false, { compile });
+		assert.equal(rs.get(), null);
+		assert.equal(rs.get(), 7);
+		assert.throws(() => rs.get(), /oops/);
+	});
+}
+
+test("exports whose names are not identifiers are wrapped without naming a function after them", () => {
+	const add = (x, y) => x + y;
+	const names = ["new", "a-b", 'x"); throw 1; ("'];
+	const rs = glue.wrap(mockInstance(Object.fromEntries(names.map((name) => [name, [info(), add]]))), false, { compile: true });
+	for (const name of names) assert.equal(rs[name](1, 2), 3);
+	assert.throws(() => rs.new(1), /new: expected 2 arguments, got 1/);
+});
@@ -0,0 +1,50 @@
+// Tests of the minifier that build.rs applies to the glue code with the `minify` feature. Its behavior on the glue
+// code itself is tested by running the JavaScript tests on a minified copy (see tests/node.rs).
+
+#[path = "../build/minify.rs"]
+mod minify;
+
+use minify::minify_js;
+
+#[test]
+fn removes_comments_and_whitespace() {
+    let code = "// Add\nfunction add(a, b) {\n\t/* sum */\n\treturn a + b;\n}\n";
+    assert_eq!(minify_js(code), "function add(a,b){return a+b;}\n");
+}
+
+#[test]
+fn keeps_line_breaks_that_may_end_statements() {
+    assert_eq!(minify_js("let a = b\nlet c = d\n"), "let a=b\nlet c=d\n");
+    assert_eq!(minify_js("a =\n  f(b,\n  c)\n"), "a=f(b,c)\n");
+}
+
+#[test]
+fn keeps_tokens_separate() {
+    assert_eq!(minify_js("return x"), "return x\n");
+    assert_eq!(minify_js("a + +b; a - -b"), "a+ +b;a- -b\n");
+}
+
+#[test]
+fn copies_literals_verbatim() {
+    let code = "const s = \"a  // b\"; const t = `x  ${ f( 1 ) }  y`;";
+    assert_eq!(
+        minify_js(code),
+        "const s=\"a  // b\";const t=`x  ${f(1)}  y`;\n"
+    );
+}
+
+#[test]
+fn distinguishes_regular_expressions_from_division() {
+    // Division after operands
+    assert_eq!(minify_js("x = (a) / b / c;"), "x=(a)/b/c;\n");
+    assert_eq!(minify_js("x = a[0] / b;"), "x=a[0]/b;\n");
+    // Regular expressions after operators, keywords, and the conditions of statements
+    assert_eq!(minify_js("x = / +/g;"), "x=/ +/g;\n");
+    assert_eq!(minify_js("return / +/.test(s);"), "return/ +/.test(s);\n");
+    assert_eq!(minify_js("if (x) / +/.test(s);"), "if(x)/ +/.test(s);\n");
+    assert_eq!(
+        minify_js("while (f(x)) / +/.exec(s);"),
+        "while(f(x))/ +/.exec(s);\n"
+    );
+    assert_eq!(minify_js("if (x) y = (a) / b;"), "if(x)y=(a)/b;\n");
+}
@@ -0,0 +1,249 @@
+// Runs the tests of the JavaScript glue code in tests/js with Node.js, if it is installed. They are skipped
+// (with a note on stderr) otherwise.
+
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+#[path = "../build/minify.rs"]
+mod minify;
+
+fn node_available() -> bool {
+    let available = Command::new("node")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if !available {
+        eprintln!("node is not installed; skipping the JavaScript tests");
+    }
+    available
+}
+
+// The module built by `fixture`, the glue code and the copy without `_info_` exports generated for it by the
+// `to_js` binary, and the module built by `worker_fixture` and the glue code generated for it, as the environment
+// variables through which they are passed to the tests. They are written once and shared by the tests, which run
+// in parallel.
+fn fixtures() -> &'static [(&'static str, PathBuf)] {
+    static FIXTURES: OnceLock<Vec<(&str, PathBuf)>> = OnceLock::new();
+    FIXTURES.get_or_init(|| {
+        let fixture = fixture();
+        let precompiled = fixture.with_extension("js");
+        let stripped = fixture.with_extension("stripped.wasm");
+        to_js(&fixture, &[("--out", &precompiled), ("--strip", &stripped)]);
+        let worker_fixture = worker_fixture();
+        let worker_precompiled = worker_fixture.with_extension("js");
+        to_js(&worker_fixture, &[("--out", &worker_precompiled)]);
+        vec![
+            ("TO_JS_FIXTURE", fixture),
+            ("TO_JS_PRECOMPILED", precompiled),
+            ("TO_JS_STRIPPED", stripped),
+            ("TO_JS_WORKER_FIXTURE", worker_fixture),
+            ("TO_JS_WORKER_PRECOMPILED", worker_precompiled),
+        ]
+    })
+}
+
+// Run the `to_js` binary on a module, with the given options and the paths they take
+fn to_js(input: &Path, options: &[(&str, &Path)]) {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_to_js"));
+    command.arg(input);
+    for (option, path) in options {
+        command.arg(option).arg(path);
+    }
+    let status = command.status().expect("failed to run to_js");
+    assert!(status.success(), "to_js failed on {}", input.display());
+}
+
+// Run `node --test` on the tests in tests/js, with the glue code read from `glue`
+fn run_node_tests(glue: &Path) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/js");
+    let output = Command::new("node")
+        .arg("--test")
+        .arg(&dir)
+        .env("TO_JS_GLUE", glue)
+        .envs(fixtures().iter().map(|(name, path)| (name, path)))
+        .output()
+        .expect("failed to run node");
+    assert!(
+        output.status.success(),
+        "JavaScript tests failed with {}:\n{}{}",
+        glue.display(),
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+// Write out a WebAssembly module that embeds the glue code in the same way as a module built with to_js (as slice
+// descriptors exported as `JS` and `JS_SCRIPT`), for testing the loader (see tests/js/loader.test.mjs). It exports
+// `add`, which adds two numbers, and `add_imported`, which adds a number to the result of the `to_js` import
+// `imported` (which takes a u32) and the `env` import `offset`. Their type info is both returned by `_info_` exports
+// and described by a manifest (see manifest.rs).
+fn fixture() -> PathBuf {
+    const JS: u32 = 16;
+    const JS_SCRIPT: u32 = 24;
+    const IMPORT_INFO: u32 = 32;
+    const MANIFEST: u32 = 64;
+    const GLUE: u32 = 1024;
+    let script = GLUE + to_js::JS.len() as u32;
+    let end = script + to_js::JS_SCRIPT.len() as u32;
+    // Type info for a number (ArrayType::None, Transform::Identity), and for a u32 (ArrayType::U32)
+    let number = [0, 0, 0, 10, 9, 0, 0, 0];
+    let u32 = [0, 0, 0, 4, 9, 0, 0, 0];
+    let (manifest, manifest_exports) = manifest(
+        MANIFEST,
+        &[
+            (0, "add", &[number]),
+            (0, "add_imported", &[number]),
+            (1, "imported", &[u32]),
+        ],
+    );
+
+    let mut wat = String::new();
+    write!(
+        wat,
+        r#"(module
+            (import "to_js" "imported" (func $imported (param f64) (result f64)))
+            (import "env" "offset" (func $offset (result f64)))
+            (memory (export "memory") {pages})
+            (global (export "JS") i32 (i32.const {JS}))
+            (global (export "JS_SCRIPT") i32 (i32.const {JS_SCRIPT}))
+            (func (export "to_js_abi_version") (result i32) (i32.const {abi_version}))
+            (func (export "add") (param f64 f64) (result f64) (f64.add (local.get 0) (local.get 1)))
+            (func (export "add_info_") (result f64) (f64.reinterpret_i64 (i64.const {number_info})))
+            (func (export "add_imported") (param f64) (result f64)
+                (f64.add (call $imported (local.get 0)) (call $offset)))
+            (func (export "add_imported_info_") (result f64) (f64.reinterpret_i64 (i64.const {number_info})))
+            (func (export "to_js_import_imported") (result f64) (f64.reinterpret_i64 (i64.const {import_info})))
+            (data (i32.const {JS}) "{js}")
+            (data (i32.const {JS_SCRIPT}) "{js_script}")
+            (data (i32.const {IMPORT_INFO}) "{u32_info}")
+            (data (i32.const {GLUE}) "{glue_bytes}")
+            (data (i32.const {script}) "{script_bytes}")
+            {manifest_exports}
+            (@custom "to_js_manifest" "{manifest}")
+        )"#,
+        pages = end.div_ceil(65536),
+        abi_version = to_js::ABI_VERSION,
+        number_info = bits(&number),
+        u32_info = escape(&u32),
+        // A (ptr, len) pair referring to the single info of the import's argument
+        import_info = bits(&descriptor(IMPORT_INFO, 1)),
+        js = escape(&descriptor(GLUE, to_js::JS.len())),
+        js_script = escape(&descriptor(script, to_js::JS_SCRIPT.len())),
+        glue_bytes = escape(to_js::JS),
+        script_bytes = escape(to_js::JS_SCRIPT),
+        manifest = escape(&manifest),
+    )
+    .unwrap();
+
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("fixture.wasm");
+    std::fs::write(&path, wat::parse_str(wat).expect("invalid fixture")).unwrap();
+    path
+}
+
+// Write out a WebAssembly module without imports for testing `wrapWorker` (see tests/js/worker.test.mjs). It
+// exports `add`, which adds two numbers, `bytes` and `view`, which return the bytes of "hello" as a slice and as a
+// ViewRef, and `spin`, which never returns. Their type info is described by a manifest.
+fn worker_fixture() -> PathBuf {
+    const HELLO: u32 = 16;
+    const MANIFEST: u32 = 64;
+    let number = [0, 0, 0, 10, 9, 0, 0, 0];
+    let (manifest, manifest_exports) = manifest(
+        MANIFEST,
+        &[
+            (0, "add", &[number]),
+            (0, "bytes", &[[0, 0, 1, 0, 9, 0, 0, 0]]),
+            (0, "view", &[[0, 0, 1, 0, 17, 0, 0, 0]]),
+            (0, "spin", &[number]),
+        ],
+    );
+
+    let wat = format!(
+        r#"(module
+            (memory (export "memory") 1)
+            (func (export "to_js_abi_version") (result i32) (i32.const {abi_version}))
+            (func (export "add") (param f64 f64) (result f64) (f64.add (local.get 0) (local.get 1)))
+            (func (export "bytes") (result f64) (f64.reinterpret_i64 (i64.const {hello})))
+            (func (export "view") (result f64) (f64.reinterpret_i64 (i64.const {hello})))
+            (func (export "spin") (result f64) (loop $spin (br $spin)) (f64.const 0))
+            (data (i32.const {HELLO}) "hello")
+            {manifest_exports}
+            (@custom "to_js_manifest" "{manifest}")
+        )"#,
+        abi_version = to_js::ABI_VERSION,
+        hello = bits(&descriptor(HELLO, 5)),
+        manifest = escape(&manifest),
+    );
+
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("worker_fixture.wasm");
+    std::fs::write(&path, wat::parse_str(wat).expect("invalid fixture")).unwrap();
+    path
+}
+
+// The bytes of a slice descriptor: a u32 pointer and a u32 length
+fn descriptor(ptr: u32, len: usize) -> Vec<u8> {
+    [ptr.to_le_bytes(), (len as u32).to_le_bytes()].concat()
+}
+
+// A manifest entry of the given kind (0 for an export and 1 for an import)
+fn manifest_entry(kind: u8, name: &str, infos: &[[u8; 8]]) -> Vec<u8> {
+    let mut entry = vec![kind];
+    entry.extend((name.len() as u32).to_le_bytes());
+    entry.extend(name.as_bytes());
+    entry.push(infos.len() as u8);
+    entry.extend(infos.concat());
+    entry
+}
+
+// A manifest with the given entries: the contents of its custom section, and, in the text format, the statics through
+// which it is exported (a data segment for each entry, stored from `offset`, and a global holding its address)
+fn manifest(mut offset: u32, entries: &[(u8, &str, &[[u8; 8]])]) -> (Vec<u8>, String) {
+    let mut section = Vec::new();
+    let mut exports = String::new();
+    for &(kind, name, infos) in entries {
+        let entry = manifest_entry(kind, name, infos);
+        let prefix = if kind == 0 { "export" } else { "import" };
+        write!(
+            exports,
+            r#"(global (export "to_js_manifest_{prefix}_{name}") i32 (i32.const {offset}))
+            (data (i32.const {offset}) "{bytes}")
+            "#,
+            bytes = escape(&entry),
+        )
+        .unwrap();
+        offset += entry.len() as u32;
+        section.extend(entry);
+    }
+    (section, exports)
+}
+
+// Escape bytes for a string in the text format
+fn escape(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("\\{byte:02x}")).collect()
+}
+
+// The bits of the f64 whose bytes are given, as a hexadecimal integer literal in the text format
+fn bits(bytes: &[u8]) -> String {
+    format!("0x{:x}", u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+#[test]
+fn glue_code() {
+    if node_available() {
+        run_node_tests(&Path::new(env!("CARGO_MANIFEST_DIR")).join("src/lib.js"));
+    }
+}
+
+// The minified glue code (see the `minify` feature) must parse and behave identically
+#[test]
+fn minified_glue_code() {
+    if node_available() {
+        let code =
+            std::fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("src/lib.js"))
+                .unwrap();
+        let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("lib.min.js");
+        std::fs::write(&path, minify::minify_js(&code)).unwrap();
+        run_node_tests(&path);
+    }
+}
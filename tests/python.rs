@@ -0,0 +1,83 @@
+// Runs the tests of the Python decoder in tests/host_py.py with python3, if it is installed. They are skipped
+// (with a note on stderr) otherwise.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn python_available() -> bool {
+    let available = Command::new("python3")
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if !available {
+        eprintln!("python3 is not installed; skipping the Python tests");
+    }
+    available
+}
+
+// Write out a WebAssembly module exporting `add`, which returns a u32, and `text`, which returns a string, whose
+// type info is described by a `to_js_manifest` custom section (see manifest.rs)
+fn fixture() -> PathBuf {
+    let mut manifest = Vec::new();
+    for (name, info) in [
+        ("add", [0, 0, 0, 4, 9, 0, 0, 0]),
+        ("text", [0, 0, 1, 0, 12, 0, 0, 0]),
+    ] {
+        manifest.push(0);
+        manifest.extend((name.len() as u32).to_le_bytes());
+        manifest.extend(name.as_bytes());
+        manifest.push(1);
+        manifest.extend(info);
+    }
+    let wat = format!(
+        r#"(module
+            (memory (export "memory") 1)
+            (func (export "to_js_abi_version") (result i32) (i32.const {abi_version}))
+            (func (export "add") (param f64 f64) (result f64) (f64.add (local.get 0) (local.get 1)))
+            (func (export "text") (result f64) (f64.const 0))
+            (@custom "to_js_manifest" "{manifest}"))"#,
+        abi_version = to_js::ABI_VERSION,
+        manifest = manifest
+            .iter()
+            .map(|b| format!("\\{b:02x}"))
+            .collect::<String>(),
+    );
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("python_fixture.wasm");
+    std::fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+    path
+}
+
+#[test]
+fn host_py() {
+    if !python_available() {
+        return;
+    }
+    let fixture = fixture();
+    let generated = fixture.with_extension("py");
+    let output = Command::new(env!("CARGO_BIN_EXE_to_js"))
+        .arg(&fixture)
+        .arg("--python")
+        .arg(&generated)
+        .output()
+        .expect("failed to run to_js");
+    assert!(
+        output.status.success(),
+        "to_js failed on the fixture:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // Only the Python module was asked for, so no glue code is written to stdout
+    assert!(output.stdout.is_empty());
+
+    let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/host_py.py");
+    let output = Command::new("python3")
+        .arg(&script)
+        .env("TO_JS_PYTHON", &generated)
+        .output()
+        .expect("failed to run python3");
+    assert!(
+        output.status.success(),
+        "Python tests failed:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}